@@ -0,0 +1,278 @@
+//! Grids of booleans packed into `u64` words, shared by the days that
+//! compare or move whole rows of cells at once.
+
+use std::ops::Range;
+
+const WORD: usize = u64::BITS as usize;
+
+/// A grid whose rows are runs of `stride` words, bit `j % 64` of word
+/// `j / 64` holding column `j`. Bits past the last column are always unset,
+/// so that rows can be compared and counted word by word.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    nrows: usize,
+    ncols: usize,
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new(nrows: usize, ncols: usize) -> Self {
+        let stride = ncols.div_ceil(WORD);
+        Self {
+            nrows,
+            ncols,
+            stride,
+            words: vec![0; nrows * stride],
+        }
+    }
+
+    /// Parse lines of text, setting the cells holding `c`.
+    pub fn parse(data: &str, c: char) -> Self {
+        let nrows = data.lines().count();
+        let ncols = data
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or_default();
+        let mut grid = Self::new(nrows, ncols);
+        for (i, line) in data.lines().enumerate() {
+            for (j, cell) in line.chars().enumerate() {
+                grid.set(i, j, cell == c);
+            }
+        }
+        grid
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    /// Number of words in a row.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> bool {
+        assert!(j < self.ncols, "column out of bounds");
+        self.row(i)[j / WORD] >> (j % WORD) & 1 == 1
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: bool) {
+        assert!(j < self.ncols, "column out of bounds");
+        let word = &mut self.row_mut(i)[j / WORD];
+        if value {
+            *word |= 1 << (j % WORD);
+        } else {
+            *word &= !(1 << (j % WORD));
+        }
+    }
+
+    pub fn row(&self, i: usize) -> &[u64] {
+        &self.words[i * self.stride..(i + 1) * self.stride]
+    }
+
+    /// Callers must leave the bits past the last column unset.
+    pub fn row_mut(&mut self, i: usize) -> &mut [u64] {
+        &mut self.words[i * self.stride..(i + 1) * self.stride]
+    }
+
+    pub fn rows_equal(&self, a: usize, b: usize) -> bool {
+        self.row(a) == self.row(b)
+    }
+
+    /// Number of cells differing between rows `a` and `b`.
+    pub fn row_distance(&self, a: usize, b: usize) -> usize {
+        self.row(a)
+            .iter()
+            .zip(self.row(b))
+            .map(|(x, y)| (x ^ y).count_ones() as usize)
+            .sum()
+    }
+
+    /// Columns in which rows `a` and `b` differ.
+    pub fn row_differences(&self, a: usize, b: usize) -> impl Iterator<Item = usize> + '_ {
+        self.row(a)
+            .iter()
+            .zip(self.row(b))
+            .enumerate()
+            .flat_map(|(w, (x, y))| bits(x ^ y).map(move |bit| w * WORD + bit))
+    }
+
+    /// Number of set cells of row `i` within `cols`.
+    pub fn count_range(&self, i: usize, cols: Range<usize>) -> usize {
+        assert!(cols.end <= self.ncols, "column out of bounds");
+        let row = self.row(i);
+        masks(cols)
+            .map(|(w, mask)| (row[w] & mask).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn set_range(&mut self, i: usize, cols: Range<usize>, value: bool) {
+        assert!(cols.end <= self.ncols, "column out of bounds");
+        let row = self.row_mut(i);
+        for (w, mask) in masks(cols) {
+            if value {
+                row[w] |= mask;
+            } else {
+                row[w] &= !mask;
+            }
+        }
+    }
+
+    pub fn cols_equal(&self, a: usize, b: usize) -> bool {
+        (0..self.nrows).all(|i| self.get(i, a) == self.get(i, b))
+    }
+
+    pub fn count_row(&self, i: usize) -> usize {
+        self.row(i).iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Columns of the set cells of row `i`.
+    pub fn row_ones(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.row(i)
+            .iter()
+            .enumerate()
+            .flat_map(|(w, &word)| bits(word).map(move |bit| w * WORD + bit))
+    }
+
+    /// Positions of the set cells, row by row.
+    pub fn ones(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.nrows).flat_map(move |i| self.row_ones(i).map(move |j| (i, j)))
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut grid = Self::new(self.ncols, self.nrows);
+        for (i, j) in self.ones() {
+            grid.set(j, i, true);
+        }
+        grid
+    }
+}
+
+/// Words overlapping `cols`, each with the mask of the bits within.
+fn masks(cols: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+    (cols.start / WORD..cols.end.div_ceil(WORD)).map(move |w| {
+        let lo = cols.start.max(w * WORD) - w * WORD;
+        let hi = cols.end.min((w + 1) * WORD) - w * WORD;
+        let ones = u64::MAX.checked_shr((WORD - (hi - lo)) as u32).unwrap_or(0);
+        (w, ones << lo)
+    })
+}
+
+/// Indices of the set bits of a word, lowest first.
+fn bits(mut word: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        let bit = word.trailing_zeros() as usize;
+        word &= word.checked_sub(1)?;
+        Some(bit)
+    })
+}
+
+#[test]
+fn test_bit_grid() {
+    let grid = BitGrid::parse("#.#\n.#.\n#.#\n...", '#');
+    assert_eq!((grid.nrows(), grid.ncols(), grid.stride()), (4, 3, 1));
+    assert!(grid.get(0, 2) && !grid.get(1, 2));
+    assert!(grid.rows_equal(0, 2) && !grid.rows_equal(0, 1));
+    assert_eq!(grid.row_distance(0, 1), 3);
+    assert_eq!(grid.row_differences(0, 3).collect::<Vec<_>>(), [0, 2]);
+    assert!(grid.cols_equal(0, 2));
+    assert_eq!(grid.count_row(0), 2);
+    assert_eq!(grid.count_ones(), 5);
+    let transposed = grid.transpose();
+    assert_eq!(transposed, BitGrid::parse("#.#.\n.#..\n#.#.", '#'));
+    assert_eq!(transposed.transpose(), grid);
+}
+
+#[test]
+fn test_wide_rows() {
+    let mut grid = BitGrid::new(2, 130);
+    assert_eq!(grid.stride(), 3);
+    grid.set(0, 63, true);
+    grid.set(0, 64, true);
+    grid.set(1, 129, true);
+    assert_eq!(
+        grid.ones().collect::<Vec<_>>(),
+        [(0, 63), (0, 64), (1, 129)]
+    );
+    assert_eq!(
+        grid.row_differences(0, 1).collect::<Vec<_>>(),
+        [63, 64, 129]
+    );
+    grid.set(0, 64, false);
+    assert_eq!(grid.count_ones(), 2);
+    grid.set_range(1, 60..130, true);
+    assert_eq!(grid.count_range(1, 0..130), 70);
+    assert_eq!(grid.count_range(1, 63..65), 2);
+    assert_eq!(grid.count_range(0, 64..64), 0);
+    grid.set_range(1, 0..128, false);
+    assert_eq!(grid.row_ones(1).collect::<Vec<_>>(), [128, 129]);
+}
+
+#[cfg(test)]
+use proptest::prelude::*;
+
+#[cfg(test)]
+fn arb_cells() -> impl Strategy<Value = Vec<Vec<bool>>> {
+    (1..5usize, 1..150usize).prop_flat_map(|(nrows, ncols)| {
+        prop::collection::vec(prop::collection::vec(any::<bool>(), ncols), nrows)
+    })
+}
+
+#[cfg(test)]
+fn from_cells(cells: &[Vec<bool>]) -> BitGrid {
+    let mut grid = BitGrid::new(cells.len(), cells[0].len());
+    for (i, row) in cells.iter().enumerate() {
+        for (j, &cell) in row.iter().enumerate() {
+            grid.set(i, j, cell);
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn prop_rows_and_columns(cells in arb_cells()) {
+        let grid = from_cells(&cells);
+        let (nrows, ncols) = (cells.len(), cells[0].len());
+        for a in 0..nrows {
+            for b in 0..nrows {
+                let differences = (0..ncols)
+                    .filter(|&j| cells[a][j] != cells[b][j])
+                    .collect::<Vec<_>>();
+                prop_assert_eq!(grid.row_distance(a, b), differences.len());
+                prop_assert_eq!(grid.row_differences(a, b).collect::<Vec<_>>(), differences);
+                prop_assert_eq!(grid.rows_equal(a, b), cells[a] == cells[b]);
+            }
+        }
+        let transposed = grid.transpose();
+        for (i, row) in cells.iter().enumerate() {
+            for (j, &cell) in row.iter().enumerate() {
+                prop_assert_eq!(transposed.get(j, i), cell);
+            }
+        }
+        prop_assert_eq!(transposed.count_ones(), grid.count_ones());
+        for (i, row) in cells.iter().enumerate() {
+            let (lo, hi) = (ncols / 3, ncols - ncols / 4);
+            let expected = row[lo..hi].iter().filter(|&&cell| cell).count();
+            prop_assert_eq!(grid.count_range(i, lo..hi), expected);
+            let mut cleared = grid.clone();
+            cleared.set_range(i, lo..hi, false);
+            prop_assert_eq!(cleared.count_row(i), grid.count_row(i) - expected);
+        }
+        prop_assert_eq!(
+            grid.cols_equal(0, ncols - 1),
+            transposed.rows_equal(0, ncols - 1)
+        );
+    }
+}
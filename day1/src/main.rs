@@ -1,4 +1,9 @@
-use std::time::Instant;
+use aho_corasick::AhoCorasick;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    time::Instant,
+};
 
 #[cfg(test)]
 const TEST_CASE_1: &str = "1abc2
@@ -19,52 +24,172 @@ const DIGITS: [&str; 10] = [
     "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
 ];
 
-fn process_p1(data: &str) -> u32 {
-    data.lines().fold(0u32, |acc, value| {
-        let mut digits = value.chars().filter_map(|c| c.to_digit(10));
-        let first_digit = digits.next().expect("No digit found");
-        let last_digit = digits.last().unwrap_or(first_digit);
-        acc + 10 * first_digit + last_digit
-    })
+fn process_p1<R: BufRead>(reader: R) -> u64 {
+    calibrate(reader, &DigitScanner::new([])).expect("Ill defined calibration document")
 }
 
 #[test]
 fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE_1), 142)
+    assert_eq!(process_p1(TEST_CASE_1.as_bytes()), 142)
 }
 
-fn process_p2(data: &str) -> u32 {
-    data.lines().fold(0u32, |acc, value| {
-        let mut digits = value.chars().enumerate().filter_map(|(i, c)| {
-            if let Some(n) = c.to_digit(10) {
-                return Some(n);
+/// Finds digits written either as numerals or as words of a vocabulary,
+/// including overlapping ones such as `eightwo`, in a single pass.
+struct DigitScanner {
+    matcher: AhoCorasick,
+    values: Vec<u32>,
+}
+
+impl DigitScanner {
+    fn new<'a, I: IntoIterator<Item = (&'a str, u32)>>(words: I) -> Self {
+        let (patterns, values): (Vec<String>, Vec<u32>) = (0..10)
+            .map(|n| (n.to_string(), n))
+            .chain(words.into_iter().map(|(w, n)| (w.to_string(), n)))
+            .unzip();
+        Self {
+            matcher: AhoCorasick::new(patterns).expect("Invalid vocabulary"),
+            values,
+        }
+    }
+
+    fn english() -> Self {
+        Self::new(DIGITS.iter().zip(0..).map(|(&w, n)| (w, n)))
+    }
+
+    /// The digits of a line, with the byte offset at which they start.
+    fn digits<'s>(&'s self, line: &'s str) -> impl Iterator<Item = (usize, u32)> + 's {
+        self.matcher
+            .find_overlapping_iter(line)
+            .map(|m| (m.start(), self.values[m.pattern().as_usize()]))
+    }
+
+    fn calibration_value(&self, line: &str) -> Option<u32> {
+        let (first, last) =
+            self.digits(line)
+                .fold(None, |bounds: Option<((usize, u32), (usize, u32))>, d| {
+                    Some(match bounds {
+                        None => (d, d),
+                        Some((first, last)) => (first.min(d), last.max(d)),
+                    })
+                })?;
+        Some(10 * first.1 + last.1)
+    }
+}
+
+#[test]
+fn test_digit_scanner() {
+    let scanner = DigitScanner::english();
+    assert_eq!(
+        scanner
+            .digits("eightwo3")
+            .map(|(_, n)| n)
+            .collect::<Vec<_>>(),
+        [8, 2, 3]
+    );
+    assert_eq!(scanner.calibration_value("zoneight"), Some(18));
+    assert_eq!(scanner.calibration_value("héllo→nine€"), Some(99));
+    assert_eq!(scanner.calibration_value("ü"), None);
+    let french = DigitScanner::new(
+        [
+            "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf",
+        ]
+        .into_iter()
+        .zip(1..),
+    );
+    assert_eq!(french.calibration_value("xdeuxsix4unéuf"), Some(21));
+    assert_eq!(french.calibration_value("septrois"), Some(73));
+}
+
+/// Reads a calibration document line by line, yielding the line number
+/// and calibration value of each line while keeping a running total.
+struct Calibration<'s, R> {
+    reader: R,
+    scanner: &'s DigitScanner,
+    buffer: String,
+    line: usize,
+    total: u64,
+}
+
+impl<'s, R: BufRead> Calibration<'s, R> {
+    fn new(reader: R, scanner: &'s DigitScanner) -> Self {
+        Self {
+            reader,
+            scanner,
+            buffer: String::new(),
+            line: 0,
+            total: 0,
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+impl<'s, R: BufRead> Iterator for Calibration<'s, R> {
+    type Item = io::Result<(usize, u32)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.buffer.clear();
+        match self.reader.read_line(&mut self.buffer) {
+            Ok(0) => return None,
+            Ok(_) => self.line += 1,
+            Err(e) => return Some(Err(e)),
+        }
+        let line = self.buffer.trim_end_matches(['\n', '\r']);
+        Some(match self.scanner.calibration_value(line) {
+            Some(value) => {
+                self.total += value as u64;
+                Ok((self.line, value))
             }
-            DIGITS.iter().enumerate().find_map(|(n, s)| {
-                if value[i..].starts_with(s) {
-                    Some(n as u32)
-                } else {
-                    None
-                }
-            })
-        });
-        let first_digit = digits.next().expect("No digit found");
-        let last_digit = digits.last().unwrap_or(first_digit);
-        acc + 10 * first_digit + last_digit
-    })
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No digit found on line {}", self.line),
+            )),
+        })
+    }
+}
+
+fn calibrate<R: BufRead>(reader: R, scanner: &DigitScanner) -> io::Result<u64> {
+    let mut calibration = Calibration::new(reader, scanner);
+    for value in calibration.by_ref() {
+        value?;
+    }
+    Ok(calibration.total())
+}
+
+#[test]
+fn test_calibration() {
+    let scanner = DigitScanner::english();
+    let mut calibration = Calibration::new("two1nine\r\neightwothree\n".as_bytes(), &scanner);
+    assert_eq!(calibration.next().unwrap().unwrap(), (1, 29));
+    assert_eq!(calibration.next().unwrap().unwrap(), (2, 83));
+    assert!(calibration.next().is_none());
+    assert_eq!(calibration.total(), 112);
+    let error = calibrate("1\nabc\n2".as_bytes(), &scanner).unwrap_err();
+    assert_eq!(error.to_string(), "No digit found on line 2");
+    // Lines spanning several reads of a tiny buffer are put back together.
+    let document = "one2\n".repeat(100_000);
+    let reader = BufReader::with_capacity(3, document.as_bytes());
+    assert_eq!(calibrate(reader, &scanner).unwrap(), 1_200_000);
+}
+
+fn process_p2<R: BufRead>(reader: R) -> u64 {
+    calibrate(reader, &DigitScanner::english()).expect("Ill defined calibration document")
 }
 
 #[test]
 fn test_process_p2() {
-    assert_eq!(process_p2(TEST_CASE_2), 281)
+    assert_eq!(process_p2(TEST_CASE_2.as_bytes()), 281)
 }
 
 fn main() {
-    let data = std::fs::read_to_string("data/day1.txt").unwrap();
+    let open = || BufReader::new(File::open("data/day1.txt").unwrap());
     let t0 = Instant::now();
-    let result_p1 = process_p1(&data);
+    let result_p1 = process_p1(open());
     let t1 = Instant::now();
     println!("The result of p1 is {}. ({:?})", result_p1, t1 - t0);
-    let result_p2 = process_p2(&data);
+    let result_p2 = process_p2(open());
     let t2 = Instant::now();
     println!("The result of p2 is {}. ({:?})", result_p2, t2 - t1);
 }
@@ -1,6 +1,4 @@
-use std::time::Instant;
-
-use geo::{Contains, Coord, LineString, Polygon};
+use std::{env, fs, time::Instant};
 
 #[cfg(test)]
 const TEST_CASE_1: &str = "..F7.
@@ -22,205 +20,270 @@ L.L7LFJ|||||FJL7||LJ
 L7JLJL-JLJLJL--JLJ.L";
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-enum From {
-    Top,
-    Right,
-    Bottom,
-    Left,
-}
-
-fn length_to_s(
-    (i, j): (usize, usize),
-    count: usize,
-    from: From,
-    grid: &[Vec<char>],
-) -> Option<usize> {
-    match (grid[i][j], from) {
-        ('S', _) => Some(count),
-        ('.', _) => None,
-        ('|', From::Top) | ('7', From::Left) | ('F', From::Right) => {
-            if i == grid.len() - 1 {
-                None
-            } else {
-                length_to_s((i + 1, j), count + 1, From::Top, grid)
-            }
-        }
-        ('|', From::Bottom) | ('J', From::Left) | ('L', From::Right) => {
-            if i == 0 {
-                None
-            } else {
-                length_to_s((i - 1, j), count + 1, From::Bottom, grid)
-            }
-        }
-        ('L', From::Top) | ('-', From::Left) | ('F', From::Bottom) => {
-            if j == grid[0].len() - 1 {
-                None
-            } else {
-                length_to_s((i, j + 1), count + 1, From::Left, grid)
-            }
-        }
-        ('J', From::Top) | ('-', From::Right) | ('7', From::Bottom) => {
-            if j == 0 {
-                None
-            } else {
-                length_to_s((i, j - 1), count + 1, From::Right, grid)
-            }
+enum Dir {
+    North,
+    East,
+    South,
+    West,
+}
+
+const DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+impl Dir {
+    fn opposite(self) -> Self {
+        match self {
+            Self::North => Self::South,
+            Self::East => Self::West,
+            Self::South => Self::North,
+            Self::West => Self::East,
         }
+    }
+}
+
+/// The two directions a pipe connects, if it is a pipe.
+fn connections(pipe: char) -> Option<[Dir; 2]> {
+    match pipe {
+        '|' => Some([Dir::North, Dir::South]),
+        '-' => Some([Dir::East, Dir::West]),
+        'L' => Some([Dir::North, Dir::East]),
+        'J' => Some([Dir::North, Dir::West]),
+        '7' => Some([Dir::South, Dir::West]),
+        'F' => Some([Dir::South, Dir::East]),
         _ => None,
     }
 }
 
-fn process_p1(data: &str) -> usize {
-    let grid = data
-        .lines()
-        .map(|lines| lines.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let (i, j) = grid
-        .iter()
-        .enumerate()
-        .find_map(|(i, rows)| {
-            rows.iter()
-                .enumerate()
-                .find_map(|(j, c)| if c == &'S' { Some((i, j)) } else { None })
+fn pipe_connecting(a: Dir, b: Dir) -> char {
+    "|-LJ7F"
+        .chars()
+        .find(|&pipe| {
+            let dirs = connections(pipe).unwrap();
+            dirs.contains(&a) && dirs.contains(&b)
         })
-        .unwrap();
-    *[
-        {
-            if i == 0 {
-                None
-            } else {
-                length_to_s((i - 1, j), 1, From::Bottom, &grid)
-            }
-        },
-        {
-            if i == grid.len() - 1 {
-                None
-            } else {
-                length_to_s((i + 1, j), 1, From::Top, &grid)
-            }
-        },
-        {
-            if j == 0 {
-                None
-            } else {
-                length_to_s((i, j - 1), 1, From::Right, &grid)
-            }
-        },
-        {
-            if j == grid[0].len() - 1 {
-                None
-            } else {
-                length_to_s((i, j + 1), 1, From::Left, &grid)
-            }
-        },
-    ]
-    .iter()
-    .flatten()
-    .max()
-    .unwrap()
-        / 2
+        .unwrap()
 }
 
-#[test]
-fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE_1), 8)
+fn box_drawing(pipe: char) -> Option<char> {
+    match pipe {
+        '|' => Some('│'),
+        '-' => Some('─'),
+        'L' => Some('└'),
+        'J' => Some('┘'),
+        '7' => Some('┐'),
+        'F' => Some('┌'),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Tile {
+    Loop,
+    Inside,
+    Outside,
+}
+
+struct Maze {
+    grid: Vec<Vec<char>>,
+    start: (usize, usize),
+}
+
+impl From<&str> for Maze {
+    fn from(data: &str) -> Self {
+        let grid = data
+            .lines()
+            .map(|lines| lines.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let start = grid
+            .iter()
+            .enumerate()
+            .find_map(|(i, rows)| rows.iter().position(|&c| c == 'S').map(|j| (i, j)))
+            .expect("No start tile");
+        Self { grid, start }
+    }
 }
 
-fn get_loop(
-    (i, j): (usize, usize),
-    mut curr_loop: Vec<(i32, i32)>,
-    from: From,
-    grid: &[Vec<char>],
-) -> Option<Vec<(i32, i32)>> {
-    curr_loop.push((i as i32, j as i32));
-    match (grid[i][j], from) {
-        ('S', _) => Some(curr_loop),
-        ('.', _) => None,
-        ('|', From::Top) | ('7', From::Left) | ('F', From::Right) => {
-            if i == grid.len() - 1 {
-                None
-            } else {
-                get_loop((i + 1, j), curr_loop, From::Top, grid)
+impl Maze {
+    fn step(&self, (i, j): (usize, usize), dir: Dir) -> Option<(usize, usize)> {
+        let (i, j) = match dir {
+            Dir::North => (i.checked_sub(1)?, j),
+            Dir::East => (i, j + 1),
+            Dir::South => (i + 1, j),
+            Dir::West => (i, j.checked_sub(1)?),
+        };
+        self.grid.get(i)?.get(j)?;
+        Some((i, j))
+    }
+
+    /// Follow the pipes from the start tile leaving towards `dir`, and
+    /// return the tiles visited along with the direction in which the walk
+    /// comes back to the start, if it does.
+    fn follow(&self, dir: Dir) -> Option<(Vec<(usize, usize)>, Dir)> {
+        let mut tiles = vec![self.start];
+        let (mut pos, mut dir) = (self.start, dir);
+        loop {
+            pos = self.step(pos, dir)?;
+            if pos == self.start {
+                return Some((tiles, dir));
             }
+            let [a, b] = connections(self.grid[pos.0][pos.1])?;
+            dir = match dir.opposite() {
+                from if from == a => b,
+                from if from == b => a,
+                _ => return None,
+            };
+            tiles.push(pos);
         }
-        ('|', From::Bottom) | ('J', From::Left) | ('L', From::Right) => {
-            if i == 0 {
-                None
-            } else {
-                get_loop((i - 1, j), curr_loop, From::Bottom, grid)
-            }
+    }
+
+    fn pipe_at(&self, pos: (usize, usize), pipe_loop: &PipeLoop) -> char {
+        if pos == self.start {
+            pipe_loop.start_pipe
+        } else {
+            self.grid[pos.0][pos.1]
         }
-        ('L', From::Top) | ('-', From::Left) | ('F', From::Bottom) => {
-            if j == grid[0].len() - 1 {
-                None
-            } else {
-                get_loop((i, j + 1), curr_loop, From::Left, grid)
+    }
+
+    /// Tiles enclosed by the loop, found by scanning each row and counting
+    /// the loop pipes crossed that connect to the north.
+    fn enclosed(&self, pipe_loop: &PipeLoop) -> Vec<(usize, usize)> {
+        let mut on_loop = self
+            .grid
+            .iter()
+            .map(|row| vec![false; row.len()])
+            .collect::<Vec<_>>();
+        for &(i, j) in &pipe_loop.tiles {
+            on_loop[i][j] = true;
+        }
+        let mut enclosed = vec![];
+        for (i, row) in on_loop.iter().enumerate() {
+            let mut inside = false;
+            for (j, &is_loop) in row.iter().enumerate() {
+                if !is_loop {
+                    if inside {
+                        enclosed.push((i, j));
+                    }
+                } else if matches!(self.pipe_at((i, j), pipe_loop), '|' | 'L' | 'J') {
+                    inside = !inside;
+                }
             }
         }
-        ('J', From::Top) | ('-', From::Right) | ('7', From::Bottom) => {
-            if j == 0 {
-                None
-            } else {
-                get_loop((i, j - 1), curr_loop, From::Right, grid)
+        enclosed
+    }
+
+    /// Draw the maze with box-drawing characters. With `ansi`, pipes off the
+    /// loop are dimmed and enclosed and outside tiles get distinct colours;
+    /// otherwise they are drawn as `I` and `O`.
+    fn render(&self, pipe_loop: &PipeLoop, ansi: bool) -> String {
+        let mut tiles = self
+            .grid
+            .iter()
+            .map(|row| vec![Tile::Outside; row.len()])
+            .collect::<Vec<_>>();
+        for &(i, j) in &pipe_loop.tiles {
+            tiles[i][j] = Tile::Loop;
+        }
+        for (i, j) in self.enclosed(pipe_loop) {
+            tiles[i][j] = Tile::Inside;
+        }
+        let mut out = String::new();
+        for (i, row) in tiles.iter().enumerate() {
+            for (j, &tile) in row.iter().enumerate() {
+                let pipe = self.pipe_at((i, j), pipe_loop);
+                let c = box_drawing(pipe).unwrap_or(pipe);
+                match (tile, ansi) {
+                    (Tile::Loop, true) if (i, j) == self.start => {
+                        out.push_str(&format!("\x1b[1;33m{c}\x1b[0m"))
+                    }
+                    (Tile::Loop, _) => out.push(c),
+                    (Tile::Inside, true) => out.push_str(&format!("\x1b[42m{c}\x1b[0m")),
+                    (Tile::Outside, true) => out.push_str(&format!("\x1b[2;34m{c}\x1b[0m")),
+                    (Tile::Inside, false) => out.push('I'),
+                    (Tile::Outside, false) => out.push('O'),
+                }
             }
+            out.push('\n');
         }
-        _ => None,
+        out
+    }
+
+    fn trace_loop(&self) -> Option<PipeLoop> {
+        DIRS.into_iter().find_map(|dir| {
+            let (tiles, back) = self.follow(dir)?;
+            Some(PipeLoop {
+                tiles,
+                start_pipe: pipe_connecting(dir, back.opposite()),
+            })
+        })
+    }
+}
+
+/// The main loop, as the ordered tiles from the start.
+#[derive(Debug)]
+struct PipeLoop {
+    tiles: Vec<(usize, usize)>,
+    /// Shape of the pipe hidden under the start tile.
+    start_pipe: char,
+}
+
+impl PipeLoop {
+    fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Number of steps to the tile of the loop farthest from the start.
+    fn farthest(&self) -> usize {
+        self.len() / 2
+    }
+
+    /// Number of enclosed tiles, from the shoelace area and Pick's theorem.
+    fn interior(&self) -> usize {
+        let twice_area = self
+            .tiles
+            .iter()
+            .zip(self.tiles.iter().cycle().skip(1))
+            .map(|(&(i0, j0), &(i1, j1))| (i0 * j1) as isize - (i1 * j0) as isize)
+            .sum::<isize>()
+            .unsigned_abs();
+        // Pick: A = I + B / 2 - 1, with 2A >= B - 2.
+        (twice_area + 2 - self.len()) / 2
     }
 }
 
+#[test]
+fn test_trace_loop() {
+    let pipe_loop = Maze::from(TEST_CASE_1).trace_loop().unwrap();
+    assert_eq!(pipe_loop.start_pipe, 'F');
+    assert_eq!(pipe_loop.len(), 16);
+    assert_eq!(pipe_loop.tiles[..3], [(2, 0), (2, 1), (1, 1)]);
+    let pipe_loop = Maze::from(TEST_CASE_2).trace_loop().unwrap();
+    assert_eq!(pipe_loop.start_pipe, '7');
+    // A single loop long enough to overflow a recursive walk.
+    let n = 50_000;
+    let data = format!("S{}7\nL{}J", "-".repeat(n), "-".repeat(n));
+    let pipe_loop = Maze::from(data.as_str()).trace_loop().unwrap();
+    assert_eq!(pipe_loop.start_pipe, 'F');
+    assert_eq!(pipe_loop.len(), 2 * n + 4);
+    assert_eq!(pipe_loop.farthest(), n + 2);
+    assert!(Maze::from("S-7\n|.|\nL-.").trace_loop().is_none());
+}
+
+fn process_p1(data: &str) -> usize {
+    Maze::from(data)
+        .trace_loop()
+        .expect("No loop through the start tile")
+        .farthest()
+}
+
+#[test]
+fn test_process_p1() {
+    assert_eq!(process_p1(TEST_CASE_1), 8)
+}
+
 fn process_p2(data: &str) -> usize {
-    let grid = data
-        .lines()
-        .map(|lines| lines.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let (i, j) = grid
-        .iter()
-        .enumerate()
-        .find_map(|(i, rows)| {
-            rows.iter()
-                .enumerate()
-                .find_map(|(j, c)| if c == &'S' { Some((i, j)) } else { None })
-        })
-        .unwrap();
-    let main_loop = [
-        {
-            if i == 0 {
-                None
-            } else {
-                get_loop((i - 1, j), vec![], From::Bottom, &grid)
-            }
-        },
-        {
-            if i == grid.len() - 1 {
-                None
-            } else {
-                get_loop((i + 1, j), vec![], From::Top, &grid)
-            }
-        },
-        {
-            if j == 0 {
-                None
-            } else {
-                get_loop((i, j - 1), vec![], From::Right, &grid)
-            }
-        },
-        {
-            if j == grid[0].len() - 1 {
-                None
-            } else {
-                get_loop((i, j + 1), vec![], From::Left, &grid)
-            }
-        },
-    ]
-    .into_iter()
-    .flatten()
-    .max_by_key(|c| c.len())
-    .unwrap();
-    let polygon = Polygon::new(LineString::from(main_loop), vec![]);
-    (1..(grid.len() - 1))
-        .flat_map(|i| (1..(grid[0].len() - 1)).map(move |j| (i as i32, j as i32)))
-        .filter(|(i, j)| polygon.contains(&Coord { x: *i, y: *j }))
-        .count()
+    Maze::from(data)
+        .trace_loop()
+        .expect("No loop through the start tile")
+        .interior()
 }
 
 #[test]
@@ -228,8 +291,75 @@ fn test_process_p2() {
     assert_eq!(process_p2(TEST_CASE_2), 10)
 }
 
+#[test]
+fn test_enclosed() {
+    let maze = Maze::from(
+        "..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........",
+    );
+    let pipe_loop = maze.trace_loop().unwrap();
+    let enclosed = maze.enclosed(&pipe_loop);
+    assert_eq!(enclosed, [(6, 2), (6, 3), (6, 6), (6, 7)]);
+    assert_eq!(pipe_loop.interior(), 4);
+    let maze = Maze::from(TEST_CASE_2);
+    let pipe_loop = maze.trace_loop().unwrap();
+    assert_eq!(maze.enclosed(&pipe_loop).len(), pipe_loop.interior());
+    for data in ["S7\nLJ", "S--7\nL--J"] {
+        let maze = Maze::from(data);
+        let pipe_loop = maze.trace_loop().unwrap();
+        assert_eq!(pipe_loop.interior(), 0);
+        assert!(maze.enclosed(&pipe_loop).is_empty());
+        assert_eq!(process_p2(data), 0);
+    }
+}
+
+#[test]
+fn test_render() {
+    let maze = Maze::from(TEST_CASE_1);
+    let pipe_loop = maze.trace_loop().unwrap();
+    assert_eq!(
+        maze.render(&pipe_loop, false),
+        "OO┌┐O
+O┌┘│O
+┌┘I└┐
+│┌──┘
+└┘OOO
+"
+    );
+    let rendered = maze.render(&pipe_loop, true);
+    assert!(rendered.contains("\x1b[1;33m┌\x1b[0m"));
+    assert!(rendered.contains("\x1b[42m.\x1b[0m"));
+    // Junk pipes off the loop are dimmed.
+    let maze = Maze::from("-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF");
+    let pipe_loop = maze.trace_loop().unwrap();
+    let rendered = maze.render(&pipe_loop, true);
+    assert!(rendered.starts_with("\x1b[2;34m─\x1b[0m"));
+    assert_eq!(maze.render(&pipe_loop, false).lines().nth(1), Some("O┌─┐O"));
+}
+
 fn main() {
     let data = std::fs::read_to_string("data/day10.txt").unwrap();
+    let maze = Maze::from(data.as_str());
+    let pipe_loop = maze.trace_loop().expect("No loop through the start tile");
+    println!(
+        "The loop has {} tiles and encloses {}, with a {} under the start.",
+        pipe_loop.len(),
+        maze.enclosed(&pipe_loop).len(),
+        pipe_loop.start_pipe
+    );
+    // Render the maze to the terminal with `-`, or to a file.
+    match env::args().nth(1).as_deref() {
+        Some("-") => print!("{}", maze.render(&pipe_loop, true)),
+        Some(path) => fs::write(path, maze.render(&pipe_loop, false)).unwrap(),
+        None => (),
+    }
     let t0 = Instant::now();
     let result_p1 = process_p1(&data);
     let t1 = Instant::now();
@@ -1,6 +1,4 @@
-use std::time::Instant;
-
-use ndarray::{Array1, Array2, Axis};
+use std::{collections::BTreeMap, time::Instant};
 
 #[cfg(test)]
 const TEST_CASE: &str = "...#......
@@ -14,107 +12,214 @@ const TEST_CASE: &str = "...#......
 .......#..
 #...#.....";
 
-fn process_p1(data: &str) -> i32 {
-    let ncol = data.lines().next().unwrap().len();
-    let mut universe = Array2::from_shape_vec([0, ncol], vec![]).unwrap();
-    for lines in data.lines() {
-        let row_vec = lines
-            .chars()
-            .map(|c| if c == '#' { 1 } else { 0 })
+/// Only the galaxies of the image, along with the empty rows and columns
+/// that expand.
+#[derive(Debug)]
+struct Universe {
+    galaxies: Vec<(usize, usize)>,
+    empty_rows: Vec<usize>,
+    empty_cols: Vec<usize>,
+}
+
+impl From<&str> for Universe {
+    fn from(data: &str) -> Self {
+        let galaxies = data
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| line.match_indices('#').map(move |(j, _)| (i, j)))
             .collect::<Vec<_>>();
-        if !row_vec.iter().any(|c| c == &1) {
-            universe
-                .push_row(Array1::from(row_vec.clone()).view())
-                .unwrap();
+        let empty = |len: usize, occupied: &dyn Fn(&(usize, usize)) -> usize| {
+            let mut empty = vec![true; len];
+            for galaxy in &galaxies {
+                empty[occupied(galaxy)] = false;
+            }
+            (0..len).filter(|&k| empty[k]).collect::<Vec<_>>()
+        };
+        let nrows = data.lines().count();
+        let ncols = data.lines().map(str::len).max().unwrap_or_default();
+        Self {
+            empty_rows: empty(nrows, &|&(i, _)| i),
+            empty_cols: empty(ncols, &|&(_, j)| j),
+            galaxies,
         }
-        universe.push_row(Array1::from(row_vec).view()).unwrap();
     }
-    let universe = {
-        let nrows = universe.shape()[0];
-        let mut expanded_universe = Array2::from_shape_vec([nrows, 0], vec![]).unwrap();
-        for col in universe.axis_iter(Axis(1)) {
-            if !col.iter().any(|v| v == &1) {
-                expanded_universe.push_column(col).unwrap()
+}
+
+impl Universe {
+    /// Coordinates of the galaxies once every empty row and column is
+    /// replaced by `factor` of them. A factor of 0 removes them.
+    fn expanded(&self, factor: u64) -> Vec<(u64, u64)> {
+        let expand = |k: usize, empty: &[usize]| {
+            let before = empty.partition_point(|&e| e < k) as u64;
+            // `k` counts the empty lines before it once, so subtracting
+            // them after adding their expansion cannot underflow.
+            k as u64 + before * factor - before
+        };
+        self.galaxies
+            .iter()
+            .map(|&(i, j)| (expand(i, &self.empty_rows), expand(j, &self.empty_cols)))
+            .collect()
+    }
+
+    /// Sum of the distances between every pair of galaxies.
+    fn distance_sum(&self, factor: u64) -> u64 {
+        let (rows, cols) = self.expanded(factor).into_iter().unzip();
+        pairwise_sum(rows) + pairwise_sum(cols)
+    }
+
+    /// Index of and distance to the nearest other galaxy, for each galaxy.
+    fn nearest(&self, factor: u64) -> Vec<Option<(usize, u64)>> {
+        let galaxies = self.expanded(factor);
+        galaxies
+            .iter()
+            .enumerate()
+            .map(|(n, &a)| {
+                galaxies
+                    .iter()
+                    .enumerate()
+                    .filter(|&(m, _)| m != n)
+                    .map(|(m, &b)| (m, distance(a, b)))
+                    .min_by_key(|&(m, d)| (d, m))
+            })
+            .collect()
+    }
+
+    /// The two galaxies farthest apart, and their distance. The Manhattan
+    /// distance is the largest spread of either `i + j` or `i - j`.
+    fn farthest_pair(&self, factor: u64) -> Option<((usize, usize), u64)> {
+        let galaxies = self.expanded(factor);
+        let spread = |key: &dyn Fn(&(u64, u64)) -> i64| {
+            let keys = || galaxies.iter().map(key).enumerate();
+            let (lo, min) = keys().min_by_key(|&(_, k)| k)?;
+            let (hi, max) = keys().max_by_key(|&(_, k)| k)?;
+            Some(((lo, hi), max.abs_diff(min)))
+        };
+        let sum = spread(&|&(i, j)| (i + j) as i64)?;
+        let diff = spread(&|&(i, j)| i as i64 - j as i64)?;
+        Some(if sum.1 >= diff.1 { sum } else { diff })
+    }
+
+    /// Number of pairs of galaxies at each distance.
+    fn distance_histogram(&self, factor: u64) -> BTreeMap<u64, usize> {
+        let galaxies = self.expanded(factor);
+        let mut histogram = BTreeMap::new();
+        for (n, &a) in galaxies.iter().enumerate() {
+            for &b in &galaxies[n + 1..] {
+                *histogram.entry(distance(a, b)).or_default() += 1;
             }
-            expanded_universe.push_column(col).unwrap()
         }
-        expanded_universe
-    };
-    let [nrows, ncols] = [universe.shape()[0], universe.shape()[1]] as [usize; 2];
-    let galaxies = (0..nrows)
-        .flat_map(|i| (0..ncols).map(move |j| (i, j)))
-        .filter(|ind| universe[*ind] == 1)
-        .map(|(i, j)| (i as i32, j as i32))
-        .collect::<Vec<_>>();
-    (0..(galaxies.len() - 1))
-        .flat_map(|n| ((n + 1)..galaxies.len()).map(move |m| (n, m)))
-        .map(|(n, m)| {
-            let (i_n, j_n) = galaxies[n];
-            let (i_m, j_m) = galaxies[m];
-            (i_n - i_m).abs() + (j_n - j_m).abs()
+        histogram
+    }
+}
+
+fn distance(a: (u64, u64), b: (u64, u64)) -> u64 {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+/// Sum of `|a - b|` over every pair of values.
+fn pairwise_sum(mut values: Vec<u64>) -> u64 {
+    values.sort_unstable();
+    let mut prefix = 0;
+    values
+        .iter()
+        .enumerate()
+        .map(|(k, &v)| {
+            let sum = v * k as u64 - prefix;
+            prefix += v;
+            sum
         })
         .sum()
 }
 
+#[test]
+fn test_universe() {
+    let universe = Universe::from(TEST_CASE);
+    assert_eq!(universe.empty_rows, [3, 7]);
+    assert_eq!(universe.empty_cols, [2, 5, 8]);
+    assert_eq!(universe.expanded(2)[..3], [(0, 4), (1, 9), (2, 0)]);
+    assert_eq!(universe.expanded(0)[..3], [(0, 2), (1, 5), (2, 0)]);
+    for factor in [0, 1, 2, 10, 100, 1_000_000] {
+        let galaxies = universe.expanded(factor);
+        let brute_force = galaxies
+            .iter()
+            .enumerate()
+            .flat_map(|(n, a)| galaxies[n + 1..].iter().map(move |b| (a, b)))
+            .map(|(&a, &b)| distance(a, b))
+            .sum::<u64>();
+        assert_eq!(universe.distance_sum(factor), brute_force);
+    }
+}
+
+#[test]
+fn test_queries() {
+    let universe = Universe::from(TEST_CASE);
+    let nearest = universe.nearest(2);
+    // Galaxies 8 and 9 of the puzzle statement are 5 apart, and galaxy 9 is
+    // as close to galaxy 7: ties go to the first galaxy.
+    assert_eq!(nearest[7], Some((8, 5)));
+    assert_eq!(nearest[8], Some((6, 5)));
+    let histogram = universe.distance_histogram(2);
+    assert_eq!(histogram.values().sum::<usize>(), 36);
+    assert_eq!(
+        histogram.iter().map(|(d, &n)| d * n as u64).sum::<u64>(),
+        374
+    );
+    let (&max, _) = histogram.last_key_value().unwrap();
+    let ((a, b), d) = universe.farthest_pair(2).unwrap();
+    assert_eq!(d, max);
+    let galaxies = universe.expanded(2);
+    assert_eq!(distance(galaxies[a], galaxies[b]), max);
+    assert_eq!(Universe::from("#..").nearest(2), [None]);
+    assert_eq!(Universe::from("...").farthest_pair(2), None);
+}
+
+fn process_p1(data: &str) -> u64 {
+    Universe::from(data).distance_sum(2)
+}
+
 #[test]
 fn test_process_p1() {
     assert_eq!(process_p1(TEST_CASE), 374)
 }
 
-fn process_p2(data: &str, exp_factor: i64) -> i64 {
-    let ncol = data.lines().next().unwrap().len();
-    let mut universe = Array2::from_shape_vec([0, ncol], vec![]).unwrap();
-    let mut empty_rows = vec![];
-    for (i, lines) in data.lines().enumerate() {
-        let row_vec = lines
-            .chars()
-            .map(|c| if c == '#' { 1 } else { 0 })
-            .collect::<Vec<_>>();
-        if !row_vec.iter().any(|c| c == &1) {
-            empty_rows.push(i)
-        }
-        universe.push_row(Array1::from(row_vec).view()).unwrap();
-    }
-    let empty_cols = universe
-        .axis_iter(Axis(1))
-        .enumerate()
-        .filter_map(|(j, col)| {
-            if col.iter().any(|v| v == &1) {
-                None
-            } else {
-                Some(j)
-            }
-        })
-        .collect::<Vec<_>>();
-    let [nrows, ncols] = [universe.shape()[0], universe.shape()[1]] as [usize; 2];
-    let galaxies = (0..nrows)
-        .flat_map(|i| (0..ncols).map(move |j| (i, j)))
-        .filter(|ind| universe[*ind] == 1)
-        .map(|(i, j)| (i as i64, j as i64))
-        .collect::<Vec<_>>();
-    (0..(galaxies.len() - 1))
-        .flat_map(|n| ((n + 1)..galaxies.len()).map(move |m| (n, m)))
-        .map(|(n, m)| {
-            let (i_n, j_n) = galaxies[n];
-            let (i_m, j_m) = galaxies[m];
-            let r_min = empty_rows.binary_search(&(i_n as usize)).unwrap_err() as i64;
-            let r_max = empty_rows.binary_search(&(i_m as usize)).unwrap_err() as i64;
-            let c_min = empty_cols.binary_search(&(j_n as usize)).unwrap_err() as i64;
-            let c_max = empty_cols.binary_search(&(j_m as usize)).unwrap_err() as i64;
-            (i_n - i_m).abs()
-                + (j_n - j_m).abs()
-                + ((r_max - r_min).abs() + (c_max - c_min).abs()) * (exp_factor - 1)
-        })
-        .sum()
+fn process_p2(data: &str, exp_factor: u64) -> u64 {
+    Universe::from(data).distance_sum(exp_factor)
 }
 
 #[test]
 fn test_process_p2() {
-    assert_eq!(process_p2(TEST_CASE, 10), 1030)
+    assert_eq!(process_p2(TEST_CASE, 10), 1030);
+    assert_eq!(process_p2(TEST_CASE, 100), 8410)
 }
 
 fn main() {
     let data = std::fs::read_to_string("data/day11.txt").unwrap();
+    let universe = Universe::from(data.as_str());
+    let ((a, b), d) = universe.farthest_pair(1000000).unwrap();
+    println!(
+        "Galaxies {} and {} are the farthest apart, at {}.",
+        a + 1,
+        b + 1,
+        d
+    );
+    let isolated = universe
+        .nearest(1000000)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(n, nearest)| Some((n, nearest?.1)))
+        .max_by_key(|&(_, d)| d)
+        .unwrap();
+    println!(
+        "Galaxy {} is the most isolated, {} from its nearest neighbour.",
+        isolated.0 + 1,
+        isolated.1
+    );
+    let histogram = universe.distance_histogram(2);
+    let (mode, count) = histogram.iter().max_by_key(|&(_, n)| n).unwrap();
+    println!(
+        "{} pairs are {} apart, the most common distance.",
+        count, mode
+    );
     let t0 = Instant::now();
     let result_p1 = process_p1(&data);
     let t1 = Instant::now();
@@ -1,5 +1,10 @@
 use itertools::Itertools;
-use std::time::Instant;
+use num::{BigUint, One, Zero};
+use std::{
+    iter::Sum,
+    ops::{AddAssign, Mul, SubAssign},
+    time::Instant,
+};
 
 #[cfg(test)]
 const TEST_CASE: &str = "???.### 1,1,3
@@ -9,49 +14,392 @@ const TEST_CASE: &str = "???.### 1,1,3
 ????.######..#####. 1,6,5
 ?###???????? 3,2,1";
 
-fn count_valids(record: &str, counts: &[usize]) -> usize {
-    let record = format!(".{}", record.trim_matches('.'));
-    *counts
-        .iter()
-        .fold(
-            record.chars().fold(vec![1], |mut acc, c| {
-                acc.push(if c != '#' { 1 } else { 0 });
-                acc
-            }),
-            |current, count| {
-                record
-                    .chars()
-                    .enumerate()
-                    .fold((vec![0], 0), |(mut next, mut chunk), (i, c)| {
-                        if c != '.' {
-                            chunk += 1
-                        } else {
-                            chunk = 0
-                        };
-                        if c != '#' {
-                            next.push(*next.last().unwrap())
+/// Numbers of arrangements: `usize` or `u128` for the records as given,
+/// `BigUint` once they are unfolded many times.
+trait Count:
+    Clone
+    + PartialOrd
+    + Zero
+    + One
+    + Sum
+    + for<'c> AddAssign<&'c Self>
+    + for<'c> SubAssign<&'c Self>
+    + for<'c> Mul<&'c Self, Output = Self>
+{
+}
+
+impl<C> Count for C where
+    C: Clone
+        + PartialOrd
+        + Zero
+        + One
+        + Sum
+        + for<'c> AddAssign<&'c C>
+        + for<'c> SubAssign<&'c C>
+        + for<'c> Mul<&'c C, Output = C>
+{
+}
+
+fn count_valids<C: Count>(record: &str, counts: &[usize]) -> C {
+    Record::<C>::new(record, counts).count()
+}
+
+#[test]
+fn test_count_valid() {
+    assert_eq!(count_valids::<usize>("???.###", &[1, 1, 3]), 1);
+    assert_eq!(count_valids::<u128>(".??..??...?##.", &[1, 1, 3]), 4);
+    assert_eq!(count_valids::<usize>("?#?#?#?#?#?#?#?", &[1, 3, 1, 6]), 1);
+    assert_eq!(count_valids::<usize>("????.######..#####.", &[1, 6, 5]), 4);
+    assert_eq!(count_valids::<u128>("?###????????", &[3, 2, 1]), 10);
+    assert_eq!(
+        count_valids::<BigUint>("?###????????", &[3, 2, 1]),
+        BigUint::from(10u8)
+    );
+}
+
+/// Repeat the record `factor` times joined by `separator`, and the groups
+/// as many times.
+fn unfold(record: &str, groups: &[usize], factor: usize, separator: char) -> (String, Vec<usize>) {
+    let record = std::iter::repeat_n(record, factor).join(&separator.to_string());
+    (record, groups.repeat(factor))
+}
+
+/// Number of arrangements by trying every assignment of the unknown springs.
+#[cfg(test)]
+fn brute_force(record: &str, groups: &[usize]) -> usize {
+    let unknown = record.matches('?').count();
+    (0..1 << unknown)
+        .filter(|mask| {
+            let mut k = 0;
+            let arrangement = record
+                .chars()
+                .map(|c| match c {
+                    '?' => {
+                        k += 1;
+                        if mask >> (k - 1) & 1 == 1 {
+                            '#'
                         } else {
-                            next.push(0)
-                        }
-                        if &chunk >= count && record.chars().nth(i - count).unwrap() != '#' {
-                            *next.last_mut().unwrap() += current[i - count]
+                            '.'
                         }
-                        (next, chunk)
-                    })
-                    .0
-            },
+                    }
+                    c => c,
+                })
+                .collect::<String>();
+            arrangement
+                .split('.')
+                .filter(|group| !group.is_empty())
+                .map(str::len)
+                .eq(groups.iter().copied())
+        })
+        .count()
+}
+
+#[test]
+fn test_unfolded_brute_force() {
+    assert_eq!(
+        unfold(".#", &[1], 3, '?'),
+        (".#?.#?.#".to_string(), vec![1, 1, 1])
+    );
+    for line in TEST_CASE
+        .lines()
+        .chain(["#.#?. 1,2", "?#?.#? 2,1", "??#?? 2"])
+    {
+        let (record, groups) = line.split_once(' ').unwrap();
+        let groups = groups.split(',').map(|n| n.parse().unwrap()).collect_vec();
+        for (factor, separator) in [(1, '?'), (2, '?'), (2, '.'), (2, '#'), (3, '.')] {
+            let (record, groups) = unfold(record, &groups, factor, separator);
+            if record.matches('?').count() > 16 {
+                continue;
+            }
+            let expected = brute_force(&record, &groups);
+            assert_eq!(count_valids::<u128>(&record, &groups), expected as u128);
+            let record = Record::<usize>::new(&record, &groups);
+            assert_eq!(record.arrangements().count(), expected);
+        }
+    }
+}
+
+/// A condition record, along with the number of ways to complete it from
+/// each state: at position `i`, at the start of a segment, with the groups
+/// `k..` left to place.
+#[derive(Debug)]
+struct Record<C> {
+    springs: Vec<u8>,
+    groups: Vec<usize>,
+    ways: Vec<Vec<C>>,
+}
+
+/// What every arrangement of a record agrees on for an unknown spring.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Verdict {
+    Damaged,
+    Operational,
+    Ambiguous,
+}
+
+impl<C: Count> Record<C> {
+    fn new(springs: &str, groups: &[usize]) -> Self {
+        let mut record = Self {
+            springs: springs.bytes().collect(),
+            groups: groups.to_vec(),
+            ways: vec![],
+        };
+        let (n, m) = (record.springs.len(), groups.len());
+        let mut ways = vec![vec![C::zero(); m + 1]; n + 1];
+        ways[n][m] = C::one();
+        for i in (0..n).rev() {
+            for k in 0..=m {
+                let mut count = C::zero();
+                if let Some(j) = record.operational(i) {
+                    count += &ways[j][k];
+                }
+                if let Some(j) = record.damaged(i, k) {
+                    count += &ways[j][k + 1];
+                }
+                ways[i][k] = count;
+            }
+        }
+        record.ways = ways;
+        record
+    }
+
+    fn parse(line: &str) -> Self {
+        let (springs, groups) = line.split_once(' ').expect("' ' not in line");
+        let groups = groups
+            .split(',')
+            .map(|num| num.parse::<usize>().expect("Ill defined group"))
+            .collect::<Vec<_>>();
+        Self::new(springs, &groups)
+    }
+
+    /// Next position if the spring at `i` is operational.
+    fn operational(&self, i: usize) -> Option<usize> {
+        (self.springs.get(i)? != &b'#').then_some(i + 1)
+    }
+
+    /// Next position if the group `k` of damaged springs starts at `i`,
+    /// skipping the operational spring that ends it.
+    fn damaged(&self, i: usize, k: usize) -> Option<usize> {
+        let end = i + self.groups.get(k)?;
+        let fits = end <= self.springs.len()
+            && !self.springs[i..end].contains(&b'.')
+            && self.springs.get(end) != Some(&b'#');
+        fits.then_some((end + 1).min(self.springs.len()))
+    }
+
+    fn count(&self) -> C {
+        self.ways[0][0].clone()
+    }
+
+    /// Every arrangement, lazily, in lexicographic order (`#` before `.`).
+    fn arrangements(&self) -> Arrangements<'_, C> {
+        let stack = if !self.ways[0][0].is_zero() {
+            vec![(0, 0, String::new())]
+        } else {
+            vec![]
+        };
+        Arrangements {
+            record: self,
+            stack,
+        }
+    }
+
+    /// The arrangement at `index` in the order of `arrangements`, so that
+    /// arrangements can be sampled without enumerating them.
+    fn arrangement(&self, mut index: C) -> Option<String> {
+        if index >= self.ways[0][0] {
+            return None;
+        }
+        let (mut i, mut k) = (0, 0);
+        let mut arrangement = String::new();
+        while i < self.springs.len() {
+            if let Some(j) = self.damaged(i, k) {
+                if index < self.ways[j][k + 1] {
+                    self.extend_damaged(&mut arrangement, i, k);
+                    (i, k) = (j, k + 1);
+                    continue;
+                }
+                index -= &self.ways[j][k + 1];
+            }
+            arrangement.push('.');
+            i += 1;
+        }
+        Some(arrangement)
+    }
+
+    fn extend_damaged(&self, arrangement: &mut String, i: usize, k: usize) {
+        let end = i + self.groups[k];
+        arrangement.extend(std::iter::repeat_n('#', self.groups[k]));
+        if end < self.springs.len() {
+            arrangement.push('.');
+        }
+    }
+
+    /// For each unknown spring, its position and whether every arrangement
+    /// agrees on it, or `None` if there is no arrangement.
+    fn explain(&self) -> Option<Vec<(usize, Verdict)>> {
+        let total = &self.ways[0][0];
+        if total.is_zero() {
+            return None;
+        }
+        let (n, m) = (self.springs.len(), self.groups.len());
+        // Number of ways to reach each state from the start.
+        let mut reach = vec![vec![C::zero(); m + 1]; n + 1];
+        reach[0][0] = C::one();
+        // Arrangements in which a group starts, or ends, at each position.
+        let mut starts = vec![C::zero(); n + 1];
+        let mut ends = vec![C::zero(); n + 1];
+        for i in 0..n {
+            for k in 0..=m {
+                if reach[i][k].is_zero() {
+                    continue;
+                }
+                let here = reach[i][k].clone();
+                if let Some(j) = self.operational(i) {
+                    reach[j][k] += &here;
+                }
+                if let Some(j) = self.damaged(i, k) {
+                    reach[j][k + 1] += &here;
+                    let used = here * &self.ways[j][k + 1];
+                    starts[i] += &used;
+                    ends[i + self.groups[k]] += &used;
+                }
+            }
+        }
+        let mut damaged = C::zero();
+        Some(
+            (0..n)
+                .filter_map(|i| {
+                    damaged += &starts[i];
+                    damaged -= &ends[i];
+                    let verdict = if damaged.is_zero() {
+                        Verdict::Operational
+                    } else if &damaged == total {
+                        Verdict::Damaged
+                    } else {
+                        Verdict::Ambiguous
+                    };
+                    (self.springs[i] == b'?').then_some((i, verdict))
+                })
+                .collect(),
         )
-        .last()
+    }
+}
+
+/// Depth-first walk over the states of a record that still lead to an
+/// arrangement, so that every step makes progress.
+struct Arrangements<'r, C> {
+    record: &'r Record<C>,
+    stack: Vec<(usize, usize, String)>,
+}
+
+impl<C: Count> Iterator for Arrangements<'_, C> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let record = self.record;
+        while let Some((i, k, arrangement)) = self.stack.pop() {
+            if i == record.springs.len() {
+                return Some(arrangement);
+            }
+            if let Some(j) = record
+                .operational(i)
+                .filter(|&j| !record.ways[j][k].is_zero())
+            {
+                let mut arrangement = arrangement.clone();
+                arrangement.push('.');
+                self.stack.push((j, k, arrangement));
+            }
+            if let Some(j) = record
+                .damaged(i, k)
+                .filter(|&j| !record.ways[j][k + 1].is_zero())
+            {
+                let mut arrangement = arrangement;
+                record.extend_damaged(&mut arrangement, i, k);
+                self.stack.push((j, k + 1, arrangement));
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn test_record() {
+    let record = Record::<usize>::parse("?###???????? 3,2,1");
+    assert_eq!(record.count(), 10);
+    let arrangements = record.arrangements().collect::<Vec<_>>();
+    assert_eq!(arrangements.len(), 10);
+    assert_eq!(arrangements[0], ".###.##.#...");
+    assert!(arrangements.windows(2).all(|w| w[0] < w[1]));
+    for (index, arrangement) in arrangements.iter().enumerate() {
+        assert_eq!(record.arrangement(index).as_ref(), Some(arrangement));
+        let groups = arrangement
+            .split('.')
+            .filter(|group| !group.is_empty())
+            .map(str::len)
+            .collect::<Vec<_>>();
+        assert_eq!(groups, [3, 2, 1]);
+        assert!(arrangement
+            .bytes()
+            .zip(&record.springs)
+            .all(|(a, &s)| s == b'?' || s == a));
+    }
+    assert_eq!(record.arrangement(10), None);
+    for line in TEST_CASE.lines() {
+        let record = Record::<usize>::parse(line);
+        let (springs, groups) = line.split_once(' ').unwrap();
+        let groups = groups.split(',').map(|n| n.parse().unwrap()).collect_vec();
+        assert_eq!(record.count(), brute_force(springs, &groups));
+        assert_eq!(record.arrangements().count(), record.count());
+    }
+    assert_eq!(Record::<usize>::parse("#.# 3").arrangements().next(), None);
+}
+
+#[test]
+fn test_explain() {
+    use Verdict::*;
+    let record = Record::<usize>::parse("?###???????? 3,2,1");
+    assert_eq!(
+        record.explain().unwrap(),
+        [
+            (0, Operational),
+            (4, Operational),
+            (5, Ambiguous),
+            (6, Ambiguous),
+            (7, Ambiguous),
+            (8, Ambiguous),
+            (9, Ambiguous),
+            (10, Ambiguous),
+            (11, Ambiguous),
+        ]
+    );
+    let record = Record::<usize>::parse("?#?#?#?#?#?#?#? 1,3,1,6");
+    assert!(record
+        .explain()
         .unwrap()
+        .iter()
+        .all(|&(_, verdict)| verdict != Ambiguous));
+    assert_eq!(Record::<usize>::parse("?.# 2").explain(), None);
 }
 
 #[test]
-fn test_count_valid() {
-    assert_eq!(count_valids("???.###", &[1, 1, 3]), 1);
-    assert_eq!(count_valids(".??..??...?##.", &[1, 1, 3]), 4);
-    assert_eq!(count_valids("?#?#?#?#?#?#?#?", &[1, 3, 1, 6]), 1);
-    assert_eq!(count_valids("????.######..#####.", &[1, 6, 5]), 4);
-    assert_eq!(count_valids("?###????????", &[3, 2, 1]), 10);
+fn test_unfolded_record() {
+    // Far more arrangements than fit in a u128.
+    let (springs, groups) = unfold("?###????????", &[3, 2, 1], 40, '?');
+    let record = Record::<BigUint>::new(&springs, &groups);
+    let count = record.count();
+    assert!(count > BigUint::from(u128::MAX));
+    let last = record.arrangement(&count - 1u8).unwrap();
+    assert_eq!(last.len(), springs.len());
+    assert_eq!(last.matches('#').count(), 6 * 40);
+    assert_eq!(record.arrangement(count), None);
+    assert_eq!(
+        record.arrangements().next(),
+        record.arrangement(BigUint::zero())
+    );
+    let verdicts = record.explain().unwrap();
+    assert_eq!(verdicts[0], (0, Verdict::Operational));
+    assert!(verdicts.iter().any(|&(_, v)| v == Verdict::Ambiguous));
 }
 
 fn process_p1(data: &str) -> usize {
@@ -63,7 +411,7 @@ fn process_p1(data: &str) -> usize {
                 .split(',')
                 .map(|num| num.parse::<usize>().unwrap())
                 .collect::<Vec<_>>();
-            count_valids(record, &counts)
+            count_valids::<usize>(record, &counts)
         })
         .sum()
 }
@@ -73,7 +421,7 @@ fn test_process_p1() {
     assert_eq!(process_p1(TEST_CASE), 21)
 }
 
-fn process_p2(data: &str) -> usize {
+fn process_p2<C: Count>(data: &str, factor: usize, separator: char) -> C {
     data.lines()
         .map(|l| {
             let (record, counts) = l.split_once(' ').unwrap();
@@ -81,33 +429,52 @@ fn process_p2(data: &str) -> usize {
                 .split(',')
                 .map(|num| num.parse::<usize>().unwrap())
                 .collect::<Vec<_>>();
-            let record = std::iter::repeat(record)
-                .take(5)
-                .collect::<Vec<&str>>()
-                .join("?");
-            let counts = counts
-                .iter()
-                .cycle()
-                .take(counts.len() * 5)
-                .cloned()
-                .collect_vec();
-            count_valids(&record, &counts)
+            let (record, counts) = unfold(record, &counts, factor, separator);
+            count_valids::<C>(&record, &counts)
         })
         .sum()
 }
 
 #[test]
 fn test_process_p2() {
-    assert_eq!(process_p2(TEST_CASE), 525152)
+    assert_eq!(process_p2::<u128>(TEST_CASE, 5, '?'), 525152);
+    assert_eq!(
+        process_p2::<BigUint>(TEST_CASE, 1, '?'),
+        BigUint::from(21u8)
+    );
+    assert!(process_p2::<BigUint>(TEST_CASE, 40, '?') > BigUint::from(u128::MAX));
 }
 
 fn main() {
     let data = std::fs::read_to_string("data/day12.txt").unwrap();
+    let records = data.lines().map(Record::<u128>::parse).collect::<Vec<_>>();
+    let verdicts = records
+        .iter()
+        .flat_map(|record| record.explain().expect("Record without arrangement"))
+        .map(|(_, verdict)| verdict)
+        .collect::<Vec<_>>();
+    for verdict in [Verdict::Damaged, Verdict::Operational, Verdict::Ambiguous] {
+        let n = verdicts.iter().filter(|&&v| v == verdict).count();
+        println!("{} unknown springs are {:?}.", n, verdict);
+    }
+    let record = records.iter().max_by_key(|record| record.count()).unwrap();
+    println!(
+        "One of the {} arrangements of the most ambiguous record is {}.",
+        record.count(),
+        record.arrangement(record.count() / 2).unwrap()
+    );
+    for arrangement in record.arrangements().take(3) {
+        println!("  {}", arrangement);
+    }
+    println!(
+        "Unfolded 20 times, there are {} arrangements.",
+        process_p2::<BigUint>(&data, 20, '?')
+    );
     let t0 = Instant::now();
     let result_p1 = process_p1(&data);
     let t1 = Instant::now();
     println!("The result of p1 is {}. ({:?})", result_p1, t1 - t0);
-    let result_p2 = process_p2(&data);
+    let result_p2 = process_p2::<u128>(&data, 5, '?');
     let t2 = Instant::now();
     println!("The result of p2 is {}. ({:?})", result_p2, t2 - t1);
 }
@@ -1,6 +1,6 @@
 use std::time::Instant;
 
-use ndarray::{concatenate, s, Array2, Axis};
+use bitgrid::BitGrid;
 
 #[cfg(test)]
 const TEST_CASE: &str = "#.##..##.
@@ -19,162 +19,151 @@ const TEST_CASE: &str = "#.##..##.
 ..##..###
 #....#..#";
 
-fn process_pattern(pattern: &str) -> Array2<u8> {
-    let ncols = pattern.lines().next().unwrap().len();
-    pattern.lines().fold(
-        Array2::<u8>::from_shape_vec([0, ncols], vec![]).unwrap(),
-        |pattern, line| {
-            concatenate![
-                Axis(0),
-                pattern.view(),
-                Array2::from_shape_vec(
-                    [1, ncols],
-                    line.chars()
-                        .map(|c| if c == '#' { 1 } else { 0 })
-                        .collect::<Vec<_>>()
-                )
-                .unwrap()
-                .view()
-            ]
-        },
-    )
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Orientation {
+    Horizontal,
+    Vertical,
 }
 
-fn find_horizontal_reflexion(pattern: &Array2<u8>) -> Option<usize> {
-    let nrows = pattern.shape()[0];
+/// A mismatched cell along with its mirror image.
+type Smudge = [(usize, usize); 2];
+
+#[derive(Debug, PartialEq)]
+struct Reflection {
+    orientation: Orientation,
+    /// Number of rows above, or columns left of, the line.
+    position: usize,
+    /// Cleaning either cell of each smudge fixes the reflection.
+    smudges: Vec<Smudge>,
+}
+
+impl Reflection {
+    fn mismatches(&self) -> usize {
+        self.smudges.len()
+    }
+
+    fn summary(&self) -> usize {
+        match self.orientation {
+            Orientation::Horizontal => 100 * self.position,
+            Orientation::Vertical => self.position,
+        }
+    }
+}
+
+/// Lines between rows of `pattern` with at most `k` mismatched cells, and
+/// those cells.
+fn row_reflections(pattern: &BitGrid, k: usize) -> Vec<(usize, Vec<Smudge>)> {
+    let nrows = pattern.nrows();
     (1..nrows)
-        .find(|irow| {
-            (1..(irow + 1))
-                .take_while(|i| irow + i - 1 < nrows)
-                .all(|i| pattern.slice(s![irow - i, ..]) == pattern.slice(s![irow + i - 1, ..]))
+        .filter_map(|irow| {
+            let mut smudges = vec![];
+            for i in 1..=irow.min(nrows - irow) {
+                let (above, below) = (irow - i, irow + i - 1);
+                if smudges.len() + pattern.row_distance(above, below) > k {
+                    return None;
+                }
+                smudges.extend(
+                    pattern
+                        .row_differences(above, below)
+                        .map(|j| [(above, j), (below, j)]),
+                );
+            }
+            Some((irow, smudges))
         })
-        .map(|v| v * 100)
+        .collect()
 }
 
-#[test]
-fn test_find_horizontal() {
-    let pattern = ndarray::array![
-        [0, 1, 0, 1, 0],
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-        [0, 1, 0, 1, 0],
-    ];
-    assert_eq!(find_horizontal_reflexion(&pattern), Some(200))
+/// Every reflection line of the pattern with at most `k` mismatched cells,
+/// horizontal ones first. Columns are found as the rows of the transpose.
+fn find_reflections(pattern: &BitGrid, k: usize) -> Vec<Reflection> {
+    let horizontal = row_reflections(pattern, k)
+        .into_iter()
+        .map(|(position, smudges)| Reflection {
+            orientation: Orientation::Horizontal,
+            position,
+            smudges,
+        });
+    let vertical =
+        row_reflections(&pattern.transpose(), k)
+            .into_iter()
+            .map(|(position, smudges)| Reflection {
+                orientation: Orientation::Vertical,
+                position,
+                smudges: smudges
+                    .into_iter()
+                    .map(|cells| cells.map(|(i, j)| (j, i)))
+                    .collect(),
+            });
+    horizontal.chain(vertical).collect()
 }
 
-fn find_vertical_reflexion(pattern: &Array2<u8>) -> Option<usize> {
-    let ncols = pattern.shape()[1];
-    (1..ncols).find(|icol| {
-        (1..(icol + 1))
-            .take_while(|i| icol + i - 1 < ncols)
-            .all(|i| pattern.slice(s![.., icol - i]) == pattern.slice(s![.., icol + i - 1]))
-    })
+#[test]
+fn test_find_reflections() {
+    let pattern = BitGrid::parse(".#.#.\n.....\n.....\n.#.#.", '#');
+    assert_eq!(
+        find_reflections(&pattern, 0),
+        [Reflection {
+            orientation: Orientation::Horizontal,
+            position: 2,
+            smudges: vec![]
+        }]
+    );
+    let pattern = BitGrid::parse(".#..#\n.....\n.....\n.#..#", '#');
+    let reflections = find_reflections(&pattern, 0);
+    assert_eq!(reflections.len(), 2);
+    assert_eq!(reflections[1].orientation, Orientation::Vertical);
+    assert_eq!(reflections[1].summary(), 3);
+    // A vertical line offset by one, with two smudges.
+    let reflections = find_reflections(&pattern, 2);
+    assert!(reflections.contains(&Reflection {
+        orientation: Orientation::Vertical,
+        position: 2,
+        smudges: vec![[(0, 1), (0, 2)], [(3, 1), (3, 2)]]
+    }));
+    assert!(reflections.iter().all(|r| r.mismatches() <= 2));
 }
 
 #[test]
-fn test_find_vertical() {
-    let pattern = ndarray::array![
-        [0, 1, 0, 1, 0],
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-        [0, 1, 0, 1, 0],
-    ];
-    assert!(find_vertical_reflexion(&pattern).is_none());
-    let pattern = ndarray::array![
-        [0, 1, 0, 0, 1],
-        [0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0],
-        [0, 1, 0, 0, 1],
-    ];
-    assert_eq!(find_vertical_reflexion(&pattern), Some(3))
+fn test_find_reflections_smudge() {
+    let pattern = BitGrid::parse(".#.#.\n....#\n....#\n.#...", '#');
+    assert_eq!(
+        find_reflections(&pattern, 1)
+            .into_iter()
+            .filter(|r| r.mismatches() == 1)
+            .collect::<Vec<_>>(),
+        [Reflection {
+            orientation: Orientation::Horizontal,
+            position: 2,
+            smudges: vec![[(0, 3), (3, 3)]]
+        }]
+    );
 }
 
-fn process_p1(data: &str) -> usize {
+/// Sum of the summaries of the reflections with exactly `k` smudges.
+fn summarize(data: &str, k: usize) -> usize {
     data.split("\n\n")
-        .map(process_pattern)
+        .map(|pattern| BitGrid::parse(pattern, '#'))
         .map(|pattern| {
-            find_horizontal_reflexion(&pattern)
-                .unwrap_or_else(|| find_vertical_reflexion(&pattern).unwrap())
+            find_reflections(&pattern, k)
+                .into_iter()
+                .find(|reflection| reflection.mismatches() == k)
+                .expect("No reflection")
+                .summary()
         })
         .sum()
 }
 
-#[test]
-fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE), 405)
-}
-
-fn find_horizontal_reflexion_with_smudge(pattern: &Array2<u8>) -> Option<usize> {
-    let nrows = pattern.shape()[0];
-    (1..nrows)
-        .find(|irow| {
-            let mut corrected = false;
-            (1..(irow + 1))
-                .take_while(|i| irow + i - 1 < nrows)
-                .all(|i| {
-                    let p1 = pattern.slice(s![irow - i, ..]);
-                    let p2 = pattern.slice(s![irow + i - 1, ..]);
-                    if p1 == p2 {
-                        return true;
-                    }
-                    if corrected {
-                        return false;
-                    }
-                    if p1.iter().zip(p2).map(|(v1, v2)| (v1 + v2) % 2).sum::<u8>() == 1 {
-                        corrected = true;
-                        return true;
-                    }
-                    false
-                })
-                && corrected
-        })
-        .map(|v| v * 100)
+fn process_p1(data: &str) -> usize {
+    summarize(data, 0)
 }
 
 #[test]
-fn test_find_horizontal_smudge() {
-    let pattern = ndarray::array![
-        [0, 1, 0, 1, 0],
-        [0, 0, 0, 0, 1],
-        [0, 0, 0, 0, 1],
-        [0, 1, 0, 0, 0],
-    ];
-    assert_eq!(find_horizontal_reflexion_with_smudge(&pattern), Some(200))
-}
-
-fn find_vertical_reflexion_with_smudge(pattern: &Array2<u8>) -> Option<usize> {
-    let ncols = pattern.shape()[1];
-    (1..ncols).find(|icol| {
-        let mut corrected = false;
-        (1..(icol + 1))
-            .take_while(|i| icol + i - 1 < ncols)
-            .all(|i| {
-                let p1 = pattern.slice(s![.., icol - i]);
-                let p2 = pattern.slice(s![.., icol + i - 1]);
-                if p1 == p2 {
-                    return true;
-                }
-                if corrected {
-                    return false;
-                }
-                if p1.iter().zip(p2).map(|(v1, v2)| (v1 + v2) % 2).sum::<u8>() == 1 {
-                    corrected = true;
-                    return true;
-                }
-                false
-            })
-            && corrected
-    })
+fn test_process_p1() {
+    assert_eq!(process_p1(TEST_CASE), 405)
 }
 
 fn process_p2(data: &str) -> usize {
-    data.split("\n\n")
-        .map(process_pattern)
-        .map(|pattern| {
-            find_horizontal_reflexion_with_smudge(&pattern)
-                .unwrap_or_else(|| find_vertical_reflexion_with_smudge(&pattern).unwrap())
-        })
-        .sum()
+    summarize(data, 1)
 }
 
 #[test]
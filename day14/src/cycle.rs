@@ -0,0 +1,72 @@
+/// Find where the states reached by repeatedly applying `step` to `start`
+/// start repeating, with Brent's algorithm. Returns `(start, length)` of the
+/// cycle, keeping only two states alive at a time.
+pub fn find_cycle<S: Clone + PartialEq>(start: &S, mut step: impl FnMut(&mut S)) -> (usize, usize) {
+    // Find the length by moving the tortoise to the hare at every power of two.
+    let (mut power, mut length) = (1, 1);
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    step(&mut hare);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        step(&mut hare);
+        length += 1;
+    }
+    // Then walk two states `length` apart until they meet.
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    for _ in 0..length {
+        step(&mut hare);
+    }
+    let mut offset = 0;
+    while tortoise != hare {
+        step(&mut tortoise);
+        step(&mut hare);
+        offset += 1;
+    }
+    (offset, length)
+}
+
+/// The state after `n` steps, skipping the whole cycles.
+pub fn nth_state<S: Clone + PartialEq>(start: &S, mut step: impl FnMut(&mut S), n: usize) -> S {
+    let (offset, length) = find_cycle(start, &mut step);
+    let n = if n < offset {
+        n
+    } else {
+        offset + (n - offset) % length
+    };
+    let mut state = start.clone();
+    for _ in 0..n {
+        step(&mut state);
+    }
+    state
+}
+
+#[test]
+fn test_find_cycle() {
+    let step = |x: &mut u64| *x = (*x * *x + 1) % 255;
+    for start in 0..255 {
+        // Compare with the first repeated state.
+        let mut seen = vec![];
+        let mut x = start;
+        while !seen.contains(&x) {
+            seen.push(x);
+            step(&mut x);
+        }
+        let offset = seen.iter().position(|&y| y == x).unwrap();
+        assert_eq!(find_cycle(&start, step), (offset, seen.len() - offset));
+        for n in [0, 1, 5, 100, 1000] {
+            let mut x = start;
+            for _ in 0..n {
+                step(&mut x);
+            }
+            assert_eq!(nth_state(&start, step, n), x);
+        }
+    }
+    assert_eq!(find_cycle(&7, |_| ()), (0, 1));
+    assert_eq!(nth_state(&0u64, |x| *x = (*x + 1) % 3, 1_000_000_000), 1);
+}
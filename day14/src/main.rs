@@ -1,5 +1,8 @@
-use ndarray::{concatenate, Array1, Array2, Axis};
-use std::{collections::HashMap, time::Instant};
+mod cycle;
+
+use bitgrid::BitGrid;
+use cycle::nth_state;
+use std::time::Instant;
 
 #[cfg(test)]
 const TEST_CASE: &str = "O....#....
@@ -13,281 +16,188 @@ O.#..O.#.#
 #....###..
 #OO..#....";
 
-fn process_pattern(pattern: &str) -> Array2<u8> {
-    let ncols = pattern.lines().next().unwrap().len();
-    pattern.lines().fold(
-        Array2::<u8>::from_shape_vec([0, ncols], vec![]).unwrap(),
-        |pattern, line| {
-            concatenate![
-                Axis(0),
-                pattern.view(),
-                Array2::from_shape_vec(
-                    [1, ncols],
-                    line.chars()
-                        .map(|c| match c {
-                            '#' => 2,
-                            'O' => 1,
-                            _ => 0,
-                        })
-                        .collect::<Vec<_>>()
-                )
-                .unwrap()
-                .view()
-            ]
-        },
-    )
+#[derive(Debug, Clone, PartialEq)]
+struct Platform {
+    round: BitGrid,
+    cube: BitGrid,
 }
 
-fn process_col(col: ndarray::ArrayView1<u8>) -> usize {
-    col.iter()
-        .enumerate()
-        .fold((0, 0, 0), |(sum, cur_sum, block_index), (i, v)| match v {
-            0 => (sum, cur_sum, block_index),
-            1 => (sum, cur_sum + 1, block_index),
-            _ => (
-                sum + (0..cur_sum)
-                    .map(|j| col.len() - 1 - block_index - j)
-                    .sum::<usize>(),
-                0,
-                i + 1,
-            ),
-        })
-        .0
+impl From<&str> for Platform {
+    fn from(data: &str) -> Self {
+        Self {
+            round: BitGrid::parse(data, 'O'),
+            cube: BitGrid::parse(data, '#'),
+        }
+    }
 }
 
-#[test]
-fn test_process_col() {
-    assert_eq!(
-        process_col(ndarray::array![1, 1, 0, 1, 0, 1, 0, 0, 2, 2, 2].view()),
-        10 + 9 + 8 + 7
-    );
-    assert_eq!(
-        process_col(ndarray::array![1, 1, 0, 1, 2, 1, 0, 0, 2, 2, 2].view()),
-        10 + 9 + 8 + 5
-    )
+/// Roll every round rock north, or south, as far as it goes. Rows are
+/// settled one after the other, a word of cells at a time.
+fn tilt_vertical(round: &mut BitGrid, cube: &BitGrid, north: bool) {
+    let nrows = round.nrows();
+    let next = |r: usize| {
+        if north {
+            r.checked_sub(1)
+        } else {
+            Some(r + 1).filter(|&r| r < nrows)
+        }
+    };
+    for k in 0..nrows {
+        let i = if north { k } else { nrows - 1 - k };
+        for w in 0..round.stride() {
+            let mut moving = std::mem::take(&mut round.row_mut(i)[w]);
+            let mut r = i;
+            while let Some(s) = next(r).filter(|_| moving != 0) {
+                let free = !(round.row(s)[w] | cube.row(s)[w]);
+                round.row_mut(r)[w] |= moving & !free;
+                moving &= free;
+                r = s;
+            }
+            round.row_mut(r)[w] |= moving;
+        }
+    }
 }
 
-fn process_p1(data: &str) -> usize {
-    let mut table = process_pattern(data);
-    table
-        .push_row(Array1::from_elem(table.ncols(), 2).view())
-        .unwrap();
-    table.columns().into_iter().map(process_col).sum()
+/// Roll every round rock west, or east, as far as it goes: the rocks
+/// between two cubes are counted and packed against one of them.
+fn tilt_horizontal(round: &mut BitGrid, cube: &BitGrid, west: bool) {
+    let ncols = round.ncols();
+    for i in 0..round.nrows() {
+        let mut start = 0;
+        for end in cube.row_ones(i).chain([ncols]) {
+            let n = round.count_range(i, start..end);
+            round.set_range(i, start..end, false);
+            if west {
+                round.set_range(i, start..start + n, true);
+            } else {
+                round.set_range(i, end - n..end, true);
+            }
+            start = end + 1;
+        }
+    }
 }
 
-#[test]
-fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE), 136)
-}
+impl Platform {
+    fn tilt_north(&mut self) {
+        tilt_vertical(&mut self.round, &self.cube, true);
+    }
 
-fn tilt_left(col: Vec<u8>) -> Vec<u8> {
-    let (mut cum_array, cur_sum, _) = col.iter().enumerate().fold(
-        (vec![], 0, 0),
-        |(mut cum_array, cur_sum, block_index), (i, v)| match v {
-            0 => (cum_array, cur_sum, block_index),
-            1 => (cum_array, cur_sum + 1, block_index),
-            _ => {
-                cum_array.extend((0..cur_sum).map(|_| 1));
-                cum_array.extend((cum_array.len()..i).map(|_| 0));
-                cum_array.push(2);
-                (cum_array, 0, i + 1)
-            }
-        },
-    );
-    cum_array.extend((0..cur_sum).map(|_| 1));
-    cum_array.extend((cum_array.len()..col.len()).map(|_| 0));
-    cum_array
-}
+    fn tilt_south(&mut self) {
+        tilt_vertical(&mut self.round, &self.cube, false);
+    }
 
-#[test]
-fn test_tilt_col() {
-    assert_eq!(
-        tilt_left(vec![1, 1, 0, 1, 0, 1, 0, 0, 2, 2]),
-        vec![1, 1, 1, 1, 0, 0, 0, 0, 2, 2],
-    );
-    assert_eq!(
-        tilt_left(vec![2, 1, 0, 1, 2, 1, 2, 0, 2, 1]),
-        vec![2, 1, 1, 0, 2, 1, 2, 0, 2, 1]
-    )
-}
+    fn tilt_west(&mut self) {
+        tilt_horizontal(&mut self.round, &self.cube, true);
+    }
+
+    fn tilt_east(&mut self) {
+        tilt_horizontal(&mut self.round, &self.cube, false);
+    }
+
+    fn cycle(&mut self) {
+        self.tilt_north();
+        self.tilt_west();
+        self.tilt_south();
+        self.tilt_east();
+    }
 
-fn tilt_all_north(mut table: Array2<u8>) -> Array2<u8> {
-    table.columns_mut().into_iter().for_each(|mut col| {
-        let col_vec = col.to_vec();
-        col.iter_mut()
-            .zip(tilt_left(col_vec))
-            .for_each(|(a, b)| *a = b)
-    });
-    table
+    fn load(&self) -> usize {
+        let nrows = self.round.nrows();
+        (0..nrows)
+            .map(|i| self.round.count_row(i) * (nrows - i))
+            .sum()
+    }
 }
 
+#[cfg(test)]
+const TILT_CASE: &str = ".O#.O
+O....
+#.O.O
+O..O.";
+
 #[test]
 fn test_tilt_north() {
-    assert_eq!(
-        tilt_all_north(ndarray::array![
-            [0, 1, 2, 0, 1],
-            [1, 0, 0, 0, 0],
-            [2, 0, 1, 0, 1],
-            [1, 0, 0, 1, 0]
-        ]),
-        ndarray::array![
-            [1, 1, 2, 1, 1],
-            [0, 0, 1, 0, 1],
-            [2, 0, 0, 0, 0],
-            [1, 0, 0, 0, 0]
-        ]
-    );
-}
-
-fn tilt_all_south(mut table: Array2<u8>) -> Array2<u8> {
-    table.columns_mut().into_iter().for_each(|mut col| {
-        let mut col_vec = col.to_vec();
-        col_vec.reverse();
-        let mut new_col = tilt_left(col_vec);
-        new_col.reverse();
-        col.iter_mut().zip(new_col).for_each(|(a, b)| *a = b)
-    });
-    table
+    let mut platform = Platform::from(TILT_CASE);
+    platform.tilt_north();
+    assert_eq!(platform, Platform::from("OO#OO\n..O.O\n#....\nO...."));
 }
 
 #[test]
 fn test_tilt_south() {
-    assert_eq!(
-        tilt_all_south(ndarray::array![
-            [0, 1, 2, 0, 1],
-            [1, 0, 0, 0, 0],
-            [2, 0, 1, 0, 1],
-            [1, 0, 0, 1, 0]
-        ]),
-        ndarray::array![
-            [0, 0, 2, 0, 0],
-            [1, 0, 0, 0, 0],
-            [2, 0, 0, 0, 1],
-            [1, 1, 1, 1, 1]
-        ]
-    );
-}
-
-fn tilt_all_west(mut table: Array2<u8>) -> Array2<u8> {
-    table.rows_mut().into_iter().for_each(|mut row| {
-        let row_vec = row.to_vec();
-        row.iter_mut()
-            .zip(tilt_left(row_vec))
-            .for_each(|(a, b)| *a = b)
-    });
-    table
+    let mut platform = Platform::from(TILT_CASE);
+    platform.tilt_south();
+    assert_eq!(platform, Platform::from("..#..\nO....\n#...O\nOOOOO"));
 }
 
 #[test]
 fn test_tilt_west() {
-    assert_eq!(
-        tilt_all_west(ndarray::array![
-            [0, 1, 2, 0, 1],
-            [1, 0, 0, 0, 0],
-            [2, 0, 1, 0, 1],
-            [1, 0, 0, 1, 0]
-        ]),
-        ndarray::array![
-            [1, 0, 2, 1, 0],
-            [1, 0, 0, 0, 0],
-            [2, 1, 1, 0, 0],
-            [1, 1, 0, 0, 0]
-        ]
-    )
-}
-
-fn tilt_all_east(mut table: Array2<u8>) -> Array2<u8> {
-    table.rows_mut().into_iter().for_each(|mut row| {
-        let mut row_vec = row.to_vec();
-        row_vec.reverse();
-        let mut new_row = tilt_left(row_vec);
-        new_row.reverse();
-        row.iter_mut().zip(new_row).for_each(|(a, b)| *a = b)
-    });
-    table
+    let mut platform = Platform::from(TILT_CASE);
+    platform.tilt_west();
+    assert_eq!(platform, Platform::from("O.#O.\nO....\n#OO..\nOO..."));
 }
 
 #[test]
 fn test_tilt_east() {
-    assert_eq!(
-        tilt_all_east(ndarray::array![
-            [0, 1, 2, 0, 1],
-            [1, 0, 0, 0, 0],
-            [2, 0, 1, 0, 1],
-            [1, 0, 0, 1, 0]
-        ]),
-        ndarray::array![
-            [0, 1, 2, 0, 1],
-            [0, 0, 0, 0, 1],
-            [2, 0, 0, 1, 1],
-            [0, 0, 0, 1, 1]
-        ]
-    )
+    let mut platform = Platform::from(TILT_CASE);
+    platform.tilt_east();
+    assert_eq!(platform, Platform::from(".O#.O\n....O\n#..OO\n...OO"));
 }
 
-fn tilt_cycle(table: Array2<u8>) -> Array2<u8> {
-    tilt_all_east(tilt_all_south(tilt_all_west(tilt_all_north(table))))
+#[test]
+fn test_tilt_cycle() {
+    let mut platform = Platform::from(TEST_CASE);
+    platform.cycle();
+    assert_eq!(
+        platform,
+        Platform::from(
+            ".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#...."
+        )
+    );
 }
 
 #[test]
-fn test_tilt_cycle() {
+fn test_wide_platform() {
+    // Rows spanning several words, the rocks stopping against the cubes.
+    let row = |c: char| format!("{}#{}", ".".repeat(69), c.to_string().repeat(70));
+    let mut platform = Platform::from(format!("{}\n{}", row('.'), row('O')).as_str());
+    platform.tilt_north();
+    platform.tilt_west();
     assert_eq!(
-        tilt_cycle(ndarray::array![
-            [1, 0, 0, 0, 0, 2, 0, 0, 0, 0],
-            [1, 0, 1, 1, 2, 0, 0, 0, 0, 2],
-            [0, 0, 0, 0, 0, 2, 2, 0, 0, 0],
-            [1, 1, 0, 2, 1, 0, 0, 0, 0, 1],
-            [0, 1, 0, 0, 0, 0, 0, 1, 2, 0],
-            [1, 0, 2, 0, 0, 1, 0, 2, 0, 2],
-            [0, 0, 1, 0, 0, 2, 1, 0, 0, 1],
-            [0, 0, 0, 0, 0, 0, 0, 1, 0, 0],
-            [2, 0, 0, 0, 0, 2, 2, 2, 0, 0],
-            [2, 1, 1, 0, 0, 2, 0, 0, 0, 0]
-        ]),
-        ndarray::array![
-            [0, 0, 0, 0, 0, 2, 0, 0, 0, 0],
-            [0, 0, 0, 0, 2, 0, 0, 0, 1, 2],
-            [0, 0, 0, 1, 1, 2, 2, 0, 0, 0],
-            [0, 1, 1, 2, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 1, 1, 1, 2, 0],
-            [0, 1, 2, 0, 0, 0, 1, 2, 0, 2],
-            [0, 0, 0, 0, 1, 2, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 1, 1, 1, 1],
-            [2, 0, 0, 0, 1, 2, 2, 2, 0, 0],
-            [2, 0, 0, 1, 1, 2, 0, 0, 0, 0]
-        ]
+        platform.round.ones().collect::<Vec<_>>(),
+        (70..140).map(|j| (0, j)).collect::<Vec<_>>()
     );
+    assert_eq!(platform.load(), 140);
 }
 
-fn table_load(table: &Array2<u8>) -> usize {
-    table
-        .rows()
-        .into_iter()
-        .enumerate()
-        .map(|(irow, row)| row.iter().filter(|v| v == &&1).count() * (table.ncols() - irow))
-        .sum()
+fn process_p1(data: &str) -> usize {
+    let mut platform = Platform::from(data);
+    platform.tilt_north();
+    platform.load()
+}
+
+#[test]
+fn test_process_p1() {
+    assert_eq!(process_p1(TEST_CASE), 136)
 }
 
 fn process_p2(data: &str, n: usize) -> usize {
-    let mut table = process_pattern(data);
-    let mut results = vec![table_load(&table)];
-    let mut tmap = HashMap::new();
-    let mut i = 0;
-    while i != n {
-        if let Some(j) = tmap.get(&table) {
-            return results[j + (n - i) % (i - j) + 1];
-        }
-        results.push(table_load(&table));
-        tmap.insert(table.clone(), i);
-        table = tilt_cycle(table.clone());
-        i += 1
-    }
-    panic!("error");
+    nth_state(&Platform::from(data), Platform::cycle, n).load()
 }
 
 #[test]
 fn test_process_p2() {
-    assert_eq!(process_p2(TEST_CASE, 1000), 64)
+    assert_eq!(process_p2(TEST_CASE, 1000), 64);
+    assert_eq!(process_p2(TEST_CASE, 1_000_000_000), 64);
+    assert_eq!(process_p2(TEST_CASE, 1), 87)
 }
 
 fn main() {
@@ -296,7 +206,7 @@ fn main() {
     let result_p1 = process_p1(&data);
     let t1 = Instant::now();
     println!("The result of p1 is {}. ({:?})", result_p1, t1 - t0);
-    let result_p2 = process_p2(&data, 1000);
+    let result_p2 = process_p2(&data, 1_000_000_000);
     let t2 = Instant::now();
     println!("The result of p2 is {}. ({:?})", result_p2, t2 - t1);
 }
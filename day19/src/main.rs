@@ -1,4 +1,10 @@
-use std::{cmp::Ordering, collections::HashMap, time::Instant};
+use intervals::InclusiveInterval;
+use num::{BigUint, Zero};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    time::Instant,
+};
 
 #[cfg(test)]
 const TEST_CASE: &str = "px{a<2006:qkq,m>2090:A,rfg}
@@ -19,37 +25,132 @@ hdj{m>838:A,pv}
 {x=2461,m=1339,a=466,s=291}
 {x=2127,m=1623,a=2188,s=1013}";
 
-#[derive(Debug)]
-enum Part {
-    X,
-    M,
-    A,
-    S,
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    MissingSections,
+    InvalidWorkflow(String),
+    InvalidCondition(String),
+    UnknownOperator(String),
+    InvalidValue(String),
+    DuplicateWorkflow(String),
+    UnknownWorkflow(String),
+    MissingWorkflow(String),
+    CyclicWorkflow(String),
+    InvalidParts(String),
+    DuplicateCategory(String),
+    UnratedCategory(String),
 }
 
-impl Part {
-    fn get(&self, parts: &Parts) -> usize {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::X => parts.x,
-            Self::M => parts.m,
-            Self::A => parts.a,
-            Self::S => parts.s,
+            Self::MissingSections => {
+                write!(f, "expected workflows and parts separated by a blank line")
+            }
+            Self::InvalidWorkflow(s) => write!(f, "invalid workflow `{s}`"),
+            Self::InvalidCondition(s) => write!(f, "invalid rule `{s}`"),
+            Self::UnknownOperator(s) => write!(f, "unknown operator in condition `{s}`"),
+            Self::InvalidValue(s) => write!(f, "invalid value in `{s}`"),
+            Self::DuplicateWorkflow(s) => write!(f, "workflow `{s}` is defined twice"),
+            Self::UnknownWorkflow(s) => write!(f, "workflow `{s}` is referenced but never defined"),
+            Self::MissingWorkflow(s) => write!(f, "workflow `{s}` is never defined"),
+            Self::CyclicWorkflow(s) => write!(f, "workflow `{s}` can send parts back to itself"),
+            Self::InvalidParts(s) => write!(f, "invalid parts `{s}`"),
+            Self::DuplicateCategory(s) => write!(f, "category `{s}` is rated twice"),
+            Self::UnratedCategory(s) => {
+                write!(
+                    f,
+                    "category `{s}` is checked by a rule but not rated by a part"
+                )
+            }
         }
     }
 }
 
-impl From<char> for Part {
-    fn from(value: char) -> Self {
-        match value {
-            'x' => Self::X,
-            'm' => Self::M,
-            'a' => Self::A,
-            's' => Self::S,
-            _ => panic!(""),
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+impl Op {
+    fn check(&self, lhs: usize, rhs: usize) -> bool {
+        match self {
+            Self::Lt => lhs < rhs,
+            Self::Le => lhs <= rhs,
+            Self::Gt => lhs > rhs,
+            Self::Ge => lhs >= rhs,
+            Self::Eq => lhs == rhs,
+        }
+    }
+
+    /// The values `v` for which `v op value` holds.
+    fn interval(&self, value: usize) -> InclusiveInterval {
+        match self {
+            Self::Lt => value
+                .checked_sub(1)
+                .map_or(InclusiveInterval::empty(), |hi| {
+                    InclusiveInterval::new(0, hi)
+                }),
+            Self::Le => InclusiveInterval::new(0, value),
+            Self::Gt => value
+                .checked_add(1)
+                .map_or(InclusiveInterval::empty(), |lo| {
+                    InclusiveInterval::new(lo, usize::MAX)
+                }),
+            Self::Ge => InclusiveInterval::new(value, usize::MAX),
+            Self::Eq => InclusiveInterval::new(value, value),
         }
     }
 }
 
+/// Split a condition `part op value` into its category, operator and value.
+fn parse_condition(cond: &str) -> Result<(&str, Op, usize), ParseError> {
+    let pos = cond
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|&pos| pos > 0)
+        .ok_or_else(|| ParseError::InvalidCondition(cond.to_string()))?;
+    let (category, rem) = cond.split_at(pos);
+    let (op, value) = [
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("==", Op::Eq),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+    ]
+    .into_iter()
+    .find_map(|(s, op)| rem.strip_prefix(s).map(|value| (op, value)))
+    .ok_or_else(|| ParseError::UnknownOperator(cond.to_string()))?;
+    let value = value
+        .parse()
+        .map_err(|_| ParseError::InvalidValue(cond.to_string()))?;
+    Ok((category, op, value))
+}
+
+#[test]
+fn test_parse_condition() {
+    assert_eq!(parse_condition("a<2006"), Ok(("a", Op::Lt, 2006)));
+    assert_eq!(parse_condition("shiny>=12"), Ok(("shiny", Op::Ge, 12)));
+    assert_eq!(parse_condition("x==3"), Ok(("x", Op::Eq, 3)));
+    assert_eq!(
+        parse_condition("x!=3"),
+        Err(ParseError::UnknownOperator("x!=3".to_string()))
+    );
+    assert_eq!(
+        parse_condition("x<y"),
+        Err(ParseError::InvalidValue("x<y".to_string()))
+    );
+    assert_eq!(
+        parse_condition("<3"),
+        Err(ParseError::InvalidCondition("<3".to_string()))
+    );
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Dest<'a> {
     Next(&'a str),
@@ -68,161 +169,370 @@ impl<'a> From<&'a str> for Dest<'a> {
 
 #[derive(Debug)]
 struct Rule<'a> {
-    part: Part,
-    op: Ordering,
+    part: &'a str,
+    op: Op,
     value: usize,
     dest: Dest<'a>,
 }
 
-#[allow(clippy::type_complexity)]
+impl<'a> TryFrom<&'a str> for Rule<'a> {
+    type Error = ParseError;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        let (cond, dest) = s
+            .split_once(':')
+            .ok_or_else(|| ParseError::InvalidCondition(s.to_string()))?;
+        let (part, op, value) = parse_condition(cond)?;
+        Ok(Rule {
+            part,
+            op,
+            value,
+            dest: dest.into(),
+        })
+    }
+}
+
+/// Split a range into the part matching `op value` and the (up to two)
+/// parts that do not.
 fn split_range(
-    (i, j): (usize, usize),
-    op: Ordering,
+    range: InclusiveInterval,
+    op: Op,
     value: usize,
-) -> (Option<(usize, usize)>, Option<(usize, usize)>) {
-    match op {
-        Ordering::Less => {
-            if j < value {
-                (Some((i, j)), None)
-            } else if i >= value {
-                (None, Some((i, j)))
-            } else {
-                (Some((i, value - 1)), Some((value, j)))
-            }
-        }
-        _ => {
-            if i > value {
-                (Some((i, j)), None)
-            } else if j <= value {
-                (None, Some((i, j)))
-            } else {
-                (Some((value + 1, j)), Some((i, value)))
-            }
-        }
-    }
+) -> (Option<InclusiveInterval>, Vec<InclusiveInterval>) {
+    let cond = op.interval(value);
+    let (before, after) = range.difference(&cond);
+    (
+        range.intersection(&cond),
+        before.into_iter().chain(after).collect(),
+    )
 }
 
 #[test]
 fn test_split_range() {
     assert_eq!(
-        split_range((2001, 4000), Ordering::Greater, 3000),
-        (Some((3001, 4000)), Some((2001, 3000)))
-    )
+        split_range((2001..=4000).into(), Op::Gt, 3000),
+        (Some((3001..=4000).into()), vec![(2001..=3000).into()])
+    );
+    assert_eq!(
+        split_range((1..=4000).into(), Op::Le, 4000),
+        (Some((1..=4000).into()), vec![])
+    );
+    assert_eq!(
+        split_range((1..=10).into(), Op::Eq, 5),
+        (Some((5..=5).into()), vec![(1..=4).into(), (6..=10).into()])
+    );
+    assert_eq!(
+        split_range((1..=10).into(), Op::Lt, 1),
+        (None, vec![(1..=10).into()])
+    );
+    let top = InclusiveInterval::new(usize::MAX - 1, usize::MAX);
+    assert_eq!(
+        split_range(top, Op::Eq, usize::MAX),
+        (
+            Some((usize::MAX..=usize::MAX).into()),
+            vec![(usize::MAX - 1..=usize::MAX - 1).into()]
+        )
+    );
+    assert_eq!(split_range(top, Op::Gt, usize::MAX), (None, vec![top]));
+    assert_eq!(split_range(top, Op::Le, usize::MAX), (Some(top), vec![]));
 }
 
 impl<'a> Rule<'a> {
     fn check(&self, parts: &Parts) -> Option<Dest<'a>> {
-        if self.part.get(parts).cmp(&self.value) == self.op {
+        if self.op.check(parts.get(self.part), self.value) {
             Some(self.dest)
         } else {
             None
         }
     }
-    fn split_partsrange(&self, partsrange: PartsRange) -> (Option<PartsRange>, Option<PartsRange>) {
-        let PartsRange { x, m, a, s } = partsrange;
-        match self.part {
-            Part::X => {
-                let (x1, x2) = split_range(x, self.op, self.value);
-                (
-                    x1.map(|x| PartsRange { x, m, a, s }),
-                    x2.map(|x| PartsRange { x, m, a, s }),
-                )
+    fn split_partsrange(
+        &self,
+        partsrange: PartsRange<'a>,
+    ) -> (Option<PartsRange<'a>>, Vec<PartsRange<'a>>) {
+        let Some(&range) = partsrange.0.get(self.part) else {
+            return (None, vec![partsrange]);
+        };
+        let (matched, unmatched) = split_range(range, self.op, self.value);
+        let with_range = |range| {
+            let mut partsrange = partsrange.clone();
+            partsrange.0.insert(self.part, range);
+            partsrange
+        };
+        (
+            matched.map(with_range),
+            unmatched.into_iter().map(with_range).collect(),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Workflow<'a> {
+    rules: Vec<Rule<'a>>,
+    fallback: Dest<'a>,
+}
+
+#[derive(Debug)]
+struct Ruleset<'a>(HashMap<&'a str, Workflow<'a>>);
+
+impl<'a> TryFrom<&'a str> for Ruleset<'a> {
+    type Error = ParseError;
+
+    fn try_from(data: &'a str) -> Result<Self, Self::Error> {
+        let mut workflows = HashMap::new();
+        for line in data.lines() {
+            let invalid = || ParseError::InvalidWorkflow(line.to_string());
+            let (name, rem) = line.split_once('{').ok_or_else(invalid)?;
+            let rem = rem.strip_suffix('}').ok_or_else(invalid)?;
+            let mut rules = rem.split(',');
+            let fallback = rules.next_back().filter(|s| !s.is_empty());
+            let fallback = fallback.ok_or_else(invalid)?.into();
+            let rules = rules.map(Rule::try_from).collect::<Result<_, _>>()?;
+            if workflows
+                .insert(name, Workflow { rules, fallback })
+                .is_some()
+            {
+                return Err(ParseError::DuplicateWorkflow(name.to_string()));
             }
-            Part::M => {
-                let (m1, m2) = split_range(m, self.op, self.value);
-                (
-                    m1.map(|m| PartsRange { x, m, a, s }),
-                    m2.map(|m| PartsRange { x, m, a, s }),
-                )
+        }
+        let ruleset = Self(workflows);
+        if let Some(name) = ruleset.destinations().find_map(|dest| match dest {
+            Dest::Next(name) if !ruleset.0.contains_key(name) => Some(name),
+            _ => None,
+        }) {
+            return Err(ParseError::UnknownWorkflow(name.to_string()));
+        }
+        if !ruleset.0.contains_key("in") {
+            return Err(ParseError::MissingWorkflow("in".to_string()));
+        }
+        if let Some(name) = ruleset.find_cycle() {
+            return Err(ParseError::CyclicWorkflow(name.to_string()));
+        }
+        Ok(ruleset)
+    }
+}
+
+/// Depth-first search state of a workflow.
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+impl<'a> Ruleset<'a> {
+    /// A workflow on a cycle of workflows, if any, found by a depth-first
+    /// search from each workflow in name order.
+    fn find_cycle(&self) -> Option<&'a str> {
+        fn visit<'a>(
+            ruleset: &Ruleset<'a>,
+            name: &'a str,
+            visits: &mut HashMap<&'a str, Visit>,
+        ) -> Option<&'a str> {
+            match visits.get(name) {
+                Some(Visit::InProgress) => return Some(name),
+                Some(Visit::Done) => return None,
+                None => (),
             }
-            Part::A => {
-                let (a1, a2) = split_range(a, self.op, self.value);
-                (
-                    a1.map(|a| PartsRange { x, m, a, s }),
-                    a2.map(|a| PartsRange { x, m, a, s }),
-                )
+            visits.insert(name, Visit::InProgress);
+            let workflow = &ruleset.0[name];
+            for dest in workflow
+                .rules
+                .iter()
+                .map(|rule| rule.dest)
+                .chain([workflow.fallback])
+            {
+                if let Dest::Next(next) = dest {
+                    if let Some(cyclic) = visit(ruleset, next, visits) {
+                        return Some(cyclic);
+                    }
+                }
             }
-            Part::S => {
-                let (s1, s2) = split_range(s, self.op, self.value);
-                (
-                    s1.map(|s| PartsRange { x, m, a, s }),
-                    s2.map(|s| PartsRange { x, m, a, s }),
-                )
+            visits.insert(name, Visit::Done);
+            None
+        }
+        let mut names = self.0.keys().copied().collect::<Vec<_>>();
+        names.sort_unstable();
+        let mut visits = HashMap::new();
+        names
+            .into_iter()
+            .find_map(|name| visit(self, name, &mut visits))
+    }
+
+    fn destinations(&self) -> impl Iterator<Item = Dest<'a>> + '_ {
+        self.0.values().flat_map(|workflow| {
+            workflow
+                .rules
+                .iter()
+                .map(|rule| rule.dest)
+                .chain([workflow.fallback])
+        })
+    }
+
+    fn categories(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.0
+            .values()
+            .flat_map(|workflow| workflow.rules.iter().map(|rule| rule.part))
+    }
+
+    fn accepts(&self, parts: &Parts, dest: Dest<'_>) -> bool {
+        let workflow = match dest {
+            Dest::Stop(c) => return c,
+            Dest::Next(name) => &self.0[name],
+        };
+        for rule in &workflow.rules {
+            if let Some(dest) = rule.check(parts) {
+                return self.accepts(parts, dest);
             }
         }
+        self.accepts(parts, workflow.fallback)
+    }
+
+    fn accepted_combinations(&self, partsrange: PartsRange<'a>, dest: Dest<'_>) -> BigUint {
+        let workflow = match dest {
+            Dest::Stop(c) => {
+                if c {
+                    return partsrange.count();
+                } else {
+                    return BigUint::zero();
+                }
+            }
+            Dest::Next(name) => &self.0[name],
+        };
+        let (mut count, partsranges) = workflow.rules.iter().fold(
+            (BigUint::zero(), vec![partsrange]),
+            |(mut count, partsranges), rule| {
+                let mut remaining = vec![];
+                for partsrange in partsranges {
+                    let (p1, unmatched) = rule.split_partsrange(partsrange);
+                    if let Some(partsrange) = p1 {
+                        count += self.accepted_combinations(partsrange, rule.dest)
+                    }
+                    remaining.extend(unmatched);
+                }
+                (count, remaining)
+            },
+        );
+        for partsrange in partsranges {
+            count += self.accepted_combinations(partsrange, workflow.fallback);
+        }
+        count
     }
 }
 
+#[test]
+fn test_parse_ruleset() {
+    assert!(Ruleset::try_from(TEST_CASE.split_once("\n\n").unwrap().0).is_ok());
+    assert_eq!(
+        Ruleset::try_from("in{a<3:A,R").unwrap_err(),
+        ParseError::InvalidWorkflow("in{a<3:A,R".to_string())
+    );
+    assert_eq!(
+        Ruleset::try_from("in{a<3:A,R}\nin{A}").unwrap_err(),
+        ParseError::DuplicateWorkflow("in".to_string())
+    );
+    assert_eq!(
+        Ruleset::try_from("in{a<3:px,R}").unwrap_err(),
+        ParseError::UnknownWorkflow("px".to_string())
+    );
+    assert_eq!(
+        Ruleset::try_from("px{a<3:A,R}").unwrap_err(),
+        ParseError::MissingWorkflow("in".to_string())
+    );
+    assert_eq!(
+        parse("in{a<3:in,R}\n\n{a=1}").unwrap_err(),
+        ParseError::CyclicWorkflow("in".to_string())
+    );
+    assert_eq!(
+        Ruleset::try_from("in{a<3:px,A}\npx{qq}\nqq{a>5:A,px}").unwrap_err(),
+        ParseError::CyclicWorkflow("px".to_string())
+    );
+    // Two paths reaching the same workflow are not a cycle.
+    assert!(Ruleset::try_from("in{a<3:px,qq}\npx{qq}\nqq{A}").is_ok());
+    assert_eq!(
+        Ruleset::try_from("in{a~3:A,R}").unwrap_err(),
+        ParseError::UnknownOperator("a~3".to_string())
+    );
+}
+
 #[derive(Debug)]
-struct Parts {
-    a: usize,
-    x: usize,
-    m: usize,
-    s: usize,
+struct Parts<'a>(HashMap<&'a str, usize>);
+
+impl<'a> Parts<'a> {
+    /// `parse` makes sure that parts rate every category checked by a rule.
+    fn get(&self, category: &str) -> usize {
+        *self
+            .0
+            .get(category)
+            .unwrap_or_else(|| panic!("Category {category} is not rated"))
+    }
+    fn total(&self) -> usize {
+        self.0.values().sum()
+    }
 }
 
-fn process_parts(
-    ruleset: &HashMap<&str, (Vec<Rule<'_>>, Dest<'_>)>,
-    parts: &Parts,
-    dest: Dest<'_>,
-) -> bool {
-    let (rules, dest) = match dest {
-        Dest::Stop(c) => return c,
-        Dest::Next(name) => ruleset.get(name).unwrap(),
-    };
-    for rule in rules {
-        if let Some(dest) = rule.check(parts) {
-            return process_parts(ruleset, parts, dest);
+impl<'a> TryFrom<&'a str> for Parts<'a> {
+    type Error = ParseError;
+
+    fn try_from(line: &'a str) -> Result<Self, Self::Error> {
+        let invalid = || ParseError::InvalidParts(line.to_string());
+        let ratings = line
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .ok_or_else(invalid)?;
+        let mut parts = HashMap::new();
+        for s in ratings.split(',') {
+            let (category, value) = s.split_once('=').ok_or_else(invalid)?;
+            let value = value
+                .parse::<usize>()
+                .map_err(|_| ParseError::InvalidValue(s.to_string()))?;
+            if parts.insert(category, value).is_some() {
+                return Err(ParseError::DuplicateCategory(category.to_string()));
+            }
         }
+        Ok(Self(parts))
     }
-    process_parts(ruleset, parts, *dest)
 }
 
-fn process_p1(data: &str) -> usize {
-    let (ruleset, parts) = data.split_once("\n\n").unwrap();
-    let ruleset = ruleset
+#[test]
+fn test_parse_parts() {
+    let parts = Parts::try_from("{s=2876,a=1222,x=787,m=2655}").unwrap();
+    assert_eq!(parts.get("x"), 787);
+    assert_eq!(parts.get("s"), 2876);
+    assert_eq!(
+        Parts::try_from("{x=1,x=2}").unwrap_err(),
+        ParseError::DuplicateCategory("x".to_string())
+    );
+    assert_eq!(
+        Parts::try_from("x=1").unwrap_err(),
+        ParseError::InvalidParts("x=1".to_string())
+    );
+    assert_eq!(
+        parse("in{a<2006:A,R}\n\n{x=1,m=2}").unwrap_err(),
+        ParseError::UnratedCategory("a".to_string())
+    );
+}
+
+fn parse(data: &str) -> Result<(Ruleset<'_>, Vec<Parts<'_>>), ParseError> {
+    let (ruleset, parts) = data.split_once("\n\n").ok_or(ParseError::MissingSections)?;
+    let ruleset = Ruleset::try_from(ruleset)?;
+    let parts = parts
         .lines()
-        .map(|line| {
-            let (name, rem) = line.split_once('{').unwrap();
-            let mut rules = rem.trim_end_matches('}').split(',');
-            let stop: Dest = rules.next_back().unwrap().into();
-            let rules = rules
-                .map(|s| {
-                    let (cond, dest) = s.split_once(':').unwrap();
-                    let value = cond[2..].parse::<usize>().unwrap();
-                    let part = s.chars().next().unwrap().into();
-                    let op = match s.chars().nth(1).unwrap() {
-                        '>' => Ordering::Greater,
-                        '<' => Ordering::Less,
-                        _ => panic!(""),
-                    };
-                    Rule {
-                        part,
-                        op,
-                        value,
-                        dest: dest.into(),
-                    }
-                })
-                .collect::<Vec<_>>();
-            (name, (rules, stop))
-        })
-        .collect::<HashMap<_, _>>();
+        .filter(|line| !line.is_empty())
+        .map(Parts::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    for part in &parts {
+        if let Some(category) = ruleset.categories().find(|c| !part.0.contains_key(c)) {
+            return Err(ParseError::UnratedCategory(category.to_string()));
+        }
+    }
+    Ok((ruleset, parts))
+}
+
+fn process_p1(data: &str) -> usize {
+    let (ruleset, parts) = parse(data).expect("Ill defined input");
     parts
-        .lines()
-        .map(|line| {
-            line[1..line.len() - 1]
-                .split(',')
-                .map(|s| s.split_once('=').unwrap().1.parse::<usize>().unwrap())
-                .collect::<Vec<_>>()
-        })
-        .map(|p| Parts {
-            x: p[0],
-            m: p[1],
-            a: p[2],
-            s: p[3],
-        })
-        .filter(|p| process_parts(&ruleset, p, Dest::Next("in")))
-        .map(|p| p.x + p.m + p.a + p.s)
+        .iter()
+        .filter(|p| ruleset.accepts(p, Dest::Next("in")))
+        .map(|p| p.total())
         .sum()
 }
 
@@ -231,101 +541,66 @@ fn test_process_p1() {
     assert_eq!(process_p1(TEST_CASE), 19114)
 }
 
-#[derive(Debug, Clone, Copy)]
-struct PartsRange {
-    x: (usize, usize),
-    m: (usize, usize),
-    a: (usize, usize),
-    s: (usize, usize),
-}
+#[test]
+fn test_process_p1_all_operators() {
+    let data = "in{size<=3:A,size>=10:A,size==5:A,colour>1:A,R}
 
-impl PartsRange {
-    fn count(&self) -> usize {
-        (self.x.1 - self.x.0 + 1)
-            * (self.m.1 - self.m.0 + 1)
-            * (self.a.1 - self.a.0 + 1)
-            * (self.s.1 - self.s.0 + 1)
-    }
+{size=3,colour=0}
+{colour=0,size=4}
+{size=5,colour=0}
+{size=10,colour=0}
+{colour=2,size=7}";
+    assert_eq!(process_p1(data), 3 + 5 + 10 + 9)
 }
 
-fn get_accepted_combinations(
-    ruleset: &HashMap<&str, (Vec<Rule<'_>>, Dest<'_>)>,
-    partsrange: PartsRange,
-    dest: Dest<'_>,
-) -> usize {
-    let (rules, dest) = match dest {
-        Dest::Stop(c) => {
-            if c {
-                return partsrange.count();
-            } else {
-                return 0;
-            }
-        }
-        Dest::Next(name) => ruleset.get(name).unwrap(),
-    };
-    let (mut count, partsrange) =
-        rules
-            .iter()
-            .fold((0, Some(partsrange)), |(mut count, partsrange), rule| {
-                if let Some(partsrange) = partsrange {
-                    let (p1, partsrange) = rule.split_partsrange(partsrange);
-                    if let Some(partsrange) = p1 {
-                        count += get_accepted_combinations(ruleset, partsrange, rule.dest)
-                    }
-                    return (count, partsrange);
-                }
-                (count, partsrange)
-            });
-    if let Some(partsrange) = partsrange {
-        count += get_accepted_combinations(ruleset, partsrange, *dest);
+/// One range of ratings per category.
+#[derive(Debug, Clone)]
+struct PartsRange<'a>(BTreeMap<&'a str, InclusiveInterval>);
+
+impl<'a> PartsRange<'a> {
+    fn new<I: IntoIterator<Item = &'a str>>(categories: I, range: InclusiveInterval) -> Self {
+        Self(categories.into_iter().map(|c| (c, range)).collect())
+    }
+    fn count(&self) -> BigUint {
+        self.0
+            .values()
+            .map(|range| BigUint::from(range.len()))
+            .product()
     }
-    count
 }
 
-fn process_p2(data: &str) -> usize {
-    let (ruleset, _) = data.split_once("\n\n").unwrap();
-    let ruleset = ruleset
-        .lines()
-        .map(|line| {
-            let (name, rem) = line.split_once('{').unwrap();
-            let mut rules = rem.trim_end_matches('}').split(',');
-            let stop: Dest = rules.next_back().unwrap().into();
-            let rules = rules
-                .map(|s| {
-                    let (cond, dest) = s.split_once(':').unwrap();
-                    let value = cond[2..].parse::<usize>().unwrap();
-                    let part = s.chars().next().unwrap().into();
-                    let op = match s.chars().nth(1).unwrap() {
-                        '>' => Ordering::Greater,
-                        '<' => Ordering::Less,
-                        _ => panic!(""),
-                    };
-                    Rule {
-                        part,
-                        op,
-                        value,
-                        dest: dest.into(),
-                    }
-                })
-                .collect::<Vec<_>>();
-            (name, (rules, stop))
-        })
-        .collect::<HashMap<_, _>>();
-    get_accepted_combinations(
-        &ruleset,
-        PartsRange {
-            x: (1, 4000),
-            m: (1, 4000),
-            a: (1, 4000),
-            s: (1, 4000),
-        },
+fn process_p2(data: &str) -> BigUint {
+    let (ruleset, parts) = match data.split_once("\n\n") {
+        Some(_) => parse(data).expect("Ill defined input"),
+        None => (Ruleset::try_from(data).expect("Ill defined input"), vec![]),
+    };
+    // Every category, whether checked by a rule or only rated by the parts,
+    // ranges over 1..=4000.
+    let categories = ruleset
+        .categories()
+        .chain(parts.iter().flat_map(|p| p.0.keys().copied()))
+        .collect::<BTreeSet<_>>();
+    ruleset.accepted_combinations(
+        PartsRange::new(categories, (1..=4000).into()),
         Dest::Next("in"),
     )
 }
 
 #[test]
 fn test_process_p2() {
-    assert_eq!(process_p2(TEST_CASE), 167409079868000)
+    assert_eq!(process_p2(TEST_CASE), BigUint::from(167409079868000u64))
+}
+
+#[test]
+fn test_process_p2_large() {
+    let data = (0..8)
+        .map(|i| format!("c{i}=={i}"))
+        .chain(["c0>=1".to_string()])
+        .collect::<Vec<_>>()
+        .join(":A,");
+    let data = format!("in{{{data}:A,R}}");
+    // Every combination is accepted, and 4000^8 does not fit in a u64.
+    assert_eq!(process_p2(&data), BigUint::from(4000u32).pow(8))
 }
 
 fn main() {
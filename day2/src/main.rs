@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::{collections::BTreeMap, time::Instant};
 
 #[cfg(test)]
 const TEST_CASE: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
@@ -7,85 +7,176 @@ Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
 Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
 Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
 
-fn process_p1(data: &str, [r, g, b]: [u32; 3]) -> u32 {
-    data.lines().fold(0u32, |sum, line| {
-        let (game, sets) = line.split_once(':').expect("':' not  in line");
-        let valid = sets.split(';').all(|set| {
-            for s in set.split(',') {
-                let (num, color) = s[1..]
+/// Number of cubes of each colour, either drawn or held in a bag.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Cubes<'a>(BTreeMap<&'a str, u32>);
+
+type Draw<'a> = Cubes<'a>;
+type Bag<'a> = Cubes<'a>;
+
+impl<'a> Cubes<'a> {
+    fn get(&self, colour: &str) -> u32 {
+        self.0.get(colour).copied().unwrap_or_default()
+    }
+
+    fn fits_in(&self, bag: &Bag) -> bool {
+        self.0.iter().all(|(colour, &n)| n <= bag.get(colour))
+    }
+
+    /// Product of the numbers of cubes of each of `colours`, missing
+    /// colours counting as 0.
+    fn power(&self, colours: &[&str]) -> u32 {
+        colours.iter().map(|colour| self.get(colour)).product()
+    }
+}
+
+impl<'a> FromIterator<(&'a str, u32)> for Cubes<'a> {
+    fn from_iter<T: IntoIterator<Item = (&'a str, u32)>>(iter: T) -> Self {
+        let mut cubes = Self::default();
+        for (colour, n) in iter {
+            *cubes.0.entry(colour).or_default() += n;
+        }
+        cubes
+    }
+}
+
+impl<'a> From<&'a str> for Cubes<'a> {
+    fn from(draw: &'a str) -> Self {
+        draw.split(',')
+            .map(|s| {
+                let (num, colour) = s
+                    .trim()
                     .split_once(' ')
                     .expect("' ' not in 'num color' pattern");
-                let num = num.parse::<u32>().expect("num is not a u32");
-                if match color {
-                    "red" => r,
-                    "green" => g,
-                    "blue" => b,
-                    _ => panic!("{color} not in [green,red,blue]"),
-                } < num
-                {
-                    return false;
-                }
-            }
-            true
-        });
-        if valid {
-            sum + game[5..].parse::<u32>().expect("Id is not a u32")
-        } else {
-            sum
+                (colour, num.parse::<u32>().expect("num is not a u32"))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Game<'a> {
+    id: u32,
+    draws: Vec<Draw<'a>>,
+}
+
+impl<'a> From<&'a str> for Game<'a> {
+    fn from(line: &'a str) -> Self {
+        let (game, draws) = line.split_once(':').expect("':' not  in line");
+        let id = game
+            .strip_prefix("Game ")
+            .and_then(|id| id.parse::<u32>().ok())
+            .expect("Id is not a u32");
+        let draws = draws.split(';').map(Draw::from).collect();
+        Self { id, draws }
+    }
+}
+
+impl<'a> Game<'a> {
+    fn is_possible(&self, bag: &Bag) -> bool {
+        self.draws.iter().all(|draw| draw.fits_in(bag))
+    }
+
+    /// The smallest bag with which every draw of the game is possible.
+    fn minimum_bag(&self) -> Bag<'a> {
+        let mut bag = Bag::default();
+        for (&colour, &n) in self.draws.iter().flat_map(|draw| &draw.0) {
+            let max = bag.0.entry(colour).or_default();
+            *max = n.max(*max);
         }
-    })
+        bag
+    }
+}
+
+fn parse_games(data: &str) -> Vec<Game<'_>> {
+    data.lines().map(Game::from).collect()
+}
+
+#[test]
+fn test_parse_games() {
+    let games = parse_games(TEST_CASE);
+    assert_eq!(games.len(), 5);
+    assert_eq!(
+        games[0],
+        Game {
+            id: 1,
+            draws: vec![
+                Draw::from_iter([("blue", 3), ("red", 4)]),
+                Draw::from_iter([("red", 1), ("green", 2), ("blue", 6)]),
+                Draw::from_iter([("green", 2)]),
+            ]
+        }
+    );
+    let game = Game::from("Game 42: 2 cyan, 1 magenta; 5 cyan");
+    assert_eq!(game.id, 42);
+    assert_eq!(
+        game.minimum_bag(),
+        Bag::from_iter([("cyan", 5), ("magenta", 1)])
+    );
+}
+
+/// Ids of the games possible with each of the bags.
+fn possible_games(games: &[Game], bags: &[Bag]) -> Vec<Vec<u32>> {
+    bags.iter()
+        .map(|bag| {
+            games
+                .iter()
+                .filter(|game| game.is_possible(bag))
+                .map(|game| game.id)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_possible_games() {
+    let games = parse_games(TEST_CASE);
+    let bags = [
+        Bag::from_iter([("red", 12), ("green", 13), ("blue", 14)]),
+        Bag::from_iter([("red", 4), ("green", 3), ("blue", 6)]),
+        Bag::default(),
+    ];
+    assert_eq!(
+        possible_games(&games, &bags),
+        [vec![1, 2, 5], vec![1, 2], vec![]]
+    );
+}
+
+fn process_p1(data: &str, bag: &Bag) -> u32 {
+    let games = parse_games(data);
+    possible_games(&games, std::slice::from_ref(bag))[0]
+        .iter()
+        .sum()
 }
 
 #[test]
 fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE, [12, 13, 14]), 8)
+    let bag = Bag::from_iter([("red", 12), ("green", 13), ("blue", 14)]);
+    assert_eq!(process_p1(TEST_CASE, &bag), 8)
 }
 
+const COLOURS: [&str; 3] = ["red", "green", "blue"];
+
 fn process_p2(data: &str) -> u32 {
-    data.lines().fold(0u32, |sum, line| {
-        let (_, sets) = line.split_once(':').expect("':' not  in line");
-        let [r, g, b] = sets
-            .split(';')
-            .fold([0u32; 3], |[mut r, mut g, mut b], set| {
-                for s in set.split(',') {
-                    let (num, color) = s[1..]
-                        .split_once(' ')
-                        .expect("' ' not in 'num color' pattern");
-                    let num = num.parse::<u32>().expect("num is not a u32");
-                    match color {
-                        "red" => {
-                            if num > r {
-                                r = num
-                            }
-                        }
-                        "green" => {
-                            if num > g {
-                                g = num
-                            }
-                        }
-                        "blue" => {
-                            if num > b {
-                                b = num
-                            }
-                        }
-                        _ => panic!("{color} not in [green,red,blue]"),
-                    };
-                }
-                [r, g, b]
-            });
-        sum + r * g * b
-    })
+    parse_games(data)
+        .iter()
+        .map(|game| game.minimum_bag().power(&COLOURS))
+        .sum()
 }
 
 #[test]
 fn test_process_p2() {
-    assert_eq!(process_p2(TEST_CASE), 2286)
+    assert_eq!(process_p2(TEST_CASE), 2286);
+    // A colour never drawn needs no cube of that colour.
+    assert_eq!(process_p2("Game 1: 3 red, 2 blue"), 0);
+    assert_eq!(process_p2("Game 1: 3 red, 2 blue; 1 green"), 6);
 }
 
 fn main() {
     let data = std::fs::read_to_string("data/day2.txt").unwrap();
+    let bag = Bag::from_iter([("red", 12), ("green", 13), ("blue", 14)]);
     let t0 = Instant::now();
-    let result_p1 = process_p1(&data, [12, 13, 14]);
+    let result_p1 = process_p1(&data, &bag);
     let t1 = Instant::now();
     println!("The result of p1 is {}. ({:?})", result_p1, t1 - t0);
     let result_p2 = process_p2(&data);
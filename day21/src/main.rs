@@ -63,7 +63,7 @@ fn test_process_p1() {
     assert_eq!(process_p1(TEST_CASE, 6), 16)
 }
 
-/// Part 2 is boring. It requires doing some interpolation of the data..
+// Part 2 is boring. It requires doing some interpolation of the data..
 
 fn main() {
     let data = std::fs::read_to_string("data/day22.txt").unwrap();
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, ops::Range, time::Instant};
 
 #[cfg(test)]
 const TEST_CASE: &str = "467..114..
@@ -12,58 +12,167 @@ const TEST_CASE: &str = "467..114..
 ...$.*....
 .664.598..";
 
-fn process_p1(data: &str) -> u32 {
-    let mut result = 0u32;
-    let mut lines = data.lines();
-    let mut prev_line: Option<&str> = None;
-    let mut cur_line = lines.next();
-    let mut next_line = lines.next();
-    while let Some(s) = cur_line.as_ref() {
-        result = s
-            .split(|c: char| !c.is_ascii_digit())
-            .fold((0, result), |(index, value), digit| {
-                if digit.is_empty() {
-                    return (index + 1, value);
+#[derive(Debug, PartialEq)]
+struct Number {
+    value: u32,
+    row: usize,
+    /// Columns spanned by the digits.
+    cols: Range<usize>,
+}
+
+impl Number {
+    /// Positions of the cells surrounding the number.
+    fn neighbours(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let cols = self.cols.start.saturating_sub(1)..self.cols.end + 1;
+        (self.row.saturating_sub(1)..self.row + 2)
+            .flat_map(move |row| cols.clone().map(move |col| (row, col)))
+            .filter(|&(row, col)| row != self.row || !self.cols.contains(&col))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Symbol {
+    kind: char,
+    row: usize,
+    col: usize,
+}
+
+impl Symbol {
+    fn neighbours(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.row.saturating_sub(1)..self.row + 2)
+            .flat_map(|row| (self.col.saturating_sub(1)..self.col + 2).map(move |col| (row, col)))
+            .filter(|&pos| pos != (self.row, self.col))
+    }
+}
+
+/// Engine schematic indexed by position.
+#[derive(Debug, Default)]
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    number_at: HashMap<(usize, usize), usize>,
+    symbol_at: HashMap<(usize, usize), usize>,
+}
+
+impl From<&str> for Schematic {
+    fn from(data: &str) -> Self {
+        let mut schematic = Self::default();
+        for (row, line) in data.lines().enumerate() {
+            let mut number: Option<Number> = None;
+            for (col, c) in line.chars().chain(['.']).enumerate() {
+                if let Some(digit) = c.to_digit(10) {
+                    let number = number.get_or_insert(Number {
+                        value: 0,
+                        row,
+                        cols: col..col,
+                    });
+                    number.value = 10 * number.value + digit;
+                    number.cols.end = col + 1;
+                    continue;
                 }
-                let num = digit.parse::<u32>().unwrap();
-                let next_index = index + digit.len();
-                let min_index = if index > 0 {
-                    if s.chars().nth(index - 1).unwrap() != '.' {
-                        return (next_index + 1, value + num);
-                    }
-                    index - 1
-                } else {
-                    index
-                };
-                let max_index = if next_index == s.len() {
-                    next_index - 1
-                } else {
-                    if s.chars().nth(next_index).unwrap() != '.' {
-                        return (next_index + 1, value + num);
-                    }
-                    next_index
-                };
-                for i in min_index..(max_index + 1) {
-                    if prev_line
-                        .as_ref()
-                        .map(|line| line.chars().nth(i).unwrap() != '.')
-                        .unwrap_or(false)
-                        | next_line
-                            .as_ref()
-                            .map(|line| line.chars().nth(i).unwrap() != '.')
-                            .unwrap_or(false)
-                    {
-                        return (next_index + 1, value + num);
+                if let Some(number) = number.take() {
+                    for col in number.cols.clone() {
+                        schematic
+                            .number_at
+                            .insert((row, col), schematic.numbers.len());
                     }
+                    schematic.numbers.push(number);
+                }
+                if c != '.' {
+                    schematic
+                        .symbol_at
+                        .insert((row, col), schematic.symbols.len());
+                    schematic.symbols.push(Symbol { kind: c, row, col });
                 }
-                (next_index + 1, value)
-            })
-            .1;
-        prev_line = cur_line;
-        cur_line = next_line;
-        next_line = lines.next();
+            }
+        }
+        schematic
+    }
+}
+
+impl Schematic {
+    fn symbols_adjacent_to<'a>(&'a self, number: &'a Number) -> impl Iterator<Item = &'a Symbol> {
+        number
+            .neighbours()
+            .filter_map(|pos| self.symbol_at.get(&pos).map(|&i| &self.symbols[i]))
+    }
+
+    fn numbers_adjacent_to(&self, symbol: &Symbol) -> Vec<&Number> {
+        let mut indices = symbol
+            .neighbours()
+            .filter_map(|pos| self.number_at.get(&pos).copied())
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|i| &self.numbers[i]).collect()
+    }
+
+    /// Numbers adjacent to at least one symbol.
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers
+            .iter()
+            .filter(|number| self.symbols_adjacent_to(number).next().is_some())
+    }
+
+    /// Symbols of a given kind adjacent to exactly `k` numbers, along with
+    /// those numbers.
+    fn gears(&self, kind: char, k: usize) -> impl Iterator<Item = (&Symbol, Vec<&Number>)> {
+        self.symbols
+            .iter()
+            .filter(move |symbol| symbol.kind == kind)
+            .map(|symbol| (symbol, self.numbers_adjacent_to(symbol)))
+            .filter(move |(_, numbers)| numbers.len() == k)
     }
-    result
+}
+
+#[test]
+fn test_schematic() {
+    let schematic = Schematic::from(TEST_CASE);
+    assert_eq!(schematic.numbers.len(), 10);
+    assert_eq!(
+        schematic.numbers[1],
+        Number {
+            value: 114,
+            row: 0,
+            cols: 5..8
+        }
+    );
+    assert_eq!(
+        schematic.symbols[3],
+        Symbol {
+            kind: '+',
+            row: 5,
+            col: 5
+        }
+    );
+    let values = |numbers: Vec<&Number>| numbers.iter().map(|n| n.value).collect::<Vec<_>>();
+    assert_eq!(
+        values(schematic.numbers_adjacent_to(&schematic.symbols[0])),
+        [467, 35]
+    );
+    assert_eq!(
+        schematic
+            .symbols_adjacent_to(&schematic.numbers[9])
+            .map(|s| s.kind)
+            .collect::<Vec<_>>(),
+        ['*']
+    );
+    assert_eq!(
+        schematic.symbols_adjacent_to(&schematic.numbers[1]).count(),
+        0
+    );
+    let gears = schematic.gears('*', 1).collect::<Vec<_>>();
+    assert_eq!(gears.len(), 1);
+    assert_eq!(values(gears[0].1.clone()), [617]);
+    assert_eq!(schematic.gears('$', 1).count(), 1);
+    assert_eq!(schematic.gears('#', 2).count(), 0);
+}
+
+fn process_p1(data: &str) -> u32 {
+    Schematic::from(data)
+        .part_numbers()
+        .map(|number| number.value)
+        .sum()
 }
 
 #[test]
@@ -72,86 +181,10 @@ fn test_process_p1() {
 }
 
 fn process_p2(data: &str) -> u32 {
-    let mut lines = data.lines();
-    let mut prev_line: Option<&str> = None;
-    let mut cur_line = lines.next();
-    let mut next_line = lines.next();
-    let mut row = 0;
-    let mut gears: HashMap<(i32, usize), Vec<u32>> = HashMap::new();
-    while let Some(s) = cur_line.as_ref() {
-        let mut col = 0;
-        s.split(|c: char| !c.is_ascii_digit()).for_each(|digit| {
-            if digit.is_empty() {
-                col += 1;
-                return;
-            }
-            let num = digit.parse::<u32>().unwrap();
-            let next_col = col + digit.len();
-            let min_col = if col > 0 {
-                if s.chars().nth(col - 1).unwrap() == '*' {
-                    if let Some(nums) = gears.get_mut(&(row, col - 1)) {
-                        nums.push(num)
-                    } else {
-                        gears.insert((row, col - 1), vec![num]);
-                    }
-                }
-                col - 1
-            } else {
-                col
-            };
-            let max_col = if next_col == s.len() {
-                next_col - 1
-            } else {
-                if s.chars().nth(next_col).unwrap() == '*' {
-                    if let Some(nums) = gears.get_mut(&(row, next_col)) {
-                        nums.push(num)
-                    } else {
-                        gears.insert((row, next_col), vec![num]);
-                    }
-                }
-                next_col
-            };
-            for i in min_col..(max_col + 1) {
-                if prev_line
-                    .as_ref()
-                    .map(|line| line.chars().nth(i).unwrap() == '*')
-                    .unwrap_or(false)
-                {
-                    if let Some(nums) = gears.get_mut(&(row - 1, i)) {
-                        nums.push(num);
-                    } else {
-                        gears.insert((row - 1, i), vec![num]);
-                    }
-                }
-                if next_line
-                    .as_ref()
-                    .map(|line| line.chars().nth(i).unwrap() == '*')
-                    .unwrap_or(false)
-                {
-                    if let Some(nums) = gears.get_mut(&(row + 1, i)) {
-                        nums.push(num);
-                    } else {
-                        gears.insert((row + 1, i), vec![num]);
-                    }
-                }
-            }
-            col = next_col + 1
-        });
-        row += 1;
-        prev_line = cur_line;
-        cur_line = next_line;
-        next_line = lines.next();
-    }
-    gears
-        .values()
-        .filter_map(|v| {
-            if v.len() == 2 {
-                Some(v.iter().product::<u32>())
-            } else {
-                None
-            }
-        })
-        .sum::<u32>()
+    Schematic::from(data)
+        .gears('*', 2)
+        .map(|(_, numbers)| numbers.iter().map(|n| n.value).product::<u32>())
+        .sum()
 }
 
 #[test]
@@ -1,4 +1,8 @@
-use std::{collections::HashSet, time::Instant};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    time::Instant,
+};
 
 #[cfg(test)]
 const TEST_CASE: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
@@ -8,39 +12,135 @@ Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
 Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
 
-fn process_p1(data: &str) -> usize {
-    data.lines().fold(0usize, |sum, line| {
-        let (win_nums, scratched_nums) = line
-            .split_once(':')
-            .expect("':' not  in line")
-            .1
-            .split_once('|')
-            .expect("'|' is missing");
-        let win_nums = win_nums
-            .split(' ')
-            .filter_map(|s| {
-                if s.is_empty() {
-                    return None;
-                }
-                Some(s.parse::<usize>().expect("not a valid number"))
-            })
-            .collect::<HashSet<_>>();
-        let wins = scratched_nums
-            .split(' ')
-            .filter_map(|s| {
-                if s.is_empty() {
-                    return None;
-                }
-                Some(s.parse::<usize>().expect("not a valid number"))
-            })
-            .filter(|num| win_nums.contains(num))
-            .count();
-        if wins == 0 {
-            sum
-        } else {
-            sum + 2usize.pow((wins - 1) as u32)
+fn parse_numbers(s: &str) -> impl Iterator<Item = usize> + '_ {
+    s.split_whitespace()
+        .map(|s| s.parse::<usize>().expect("not a valid number"))
+}
+
+#[derive(Debug)]
+struct Scratchcard {
+    id: usize,
+    winning: HashSet<usize>,
+    numbers: Vec<usize>,
+}
+
+impl From<&str> for Scratchcard {
+    fn from(line: &str) -> Self {
+        let (card, numbers) = line.split_once(':').expect("':' not  in line");
+        let id = card
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .expect("Id is not a valid number");
+        let (winning, numbers) = numbers.split_once('|').expect("'|' is missing");
+        Self {
+            id,
+            winning: parse_numbers(winning).collect(),
+            numbers: parse_numbers(numbers).collect(),
         }
-    })
+    }
+}
+
+impl Scratchcard {
+    fn wins(&self) -> usize {
+        self.numbers
+            .iter()
+            .filter(|num| self.winning.contains(num))
+            .count()
+    }
+
+    fn points(&self) -> usize {
+        match self.wins() {
+            0 => 0,
+            wins => 2usize.pow((wins - 1) as u32),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum CardError {
+    NonContiguousId { expected: usize, found: usize },
+    WinsPastLastCard { id: usize, wins: usize, last: usize },
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonContiguousId { expected, found } => {
+                write!(f, "expected card {expected}, found card {found}")
+            }
+            Self::WinsPastLastCard { id, wins, last } => {
+                write!(f, "card {id} wins {wins} copies, past the last card {last}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CardError {}
+
+/// Number of instances of each card, keyed by id, once every original and
+/// copied card has been scratched.
+fn cascade(cards: &[Scratchcard]) -> Result<BTreeMap<usize, usize>, CardError> {
+    let Some(first) = cards.first() else {
+        return Ok(BTreeMap::new());
+    };
+    let last = first.id + cards.len() - 1;
+    let mut copies = vec![1usize; cards.len()];
+    for (i, card) in cards.iter().enumerate() {
+        if card.id != first.id + i {
+            return Err(CardError::NonContiguousId {
+                expected: first.id + i,
+                found: card.id,
+            });
+        }
+        let wins = card.wins();
+        if card.id + wins > last {
+            return Err(CardError::WinsPastLastCard {
+                id: card.id,
+                wins,
+                last,
+            });
+        }
+        for j in (i + 1)..(i + 1 + wins) {
+            copies[j] += copies[i]
+        }
+    }
+    Ok(cards.iter().map(|card| card.id).zip(copies).collect())
+}
+
+#[test]
+fn test_cascade() {
+    let cards = TEST_CASE.lines().map(Scratchcard::from).collect::<Vec<_>>();
+    assert_eq!(
+        cascade(&cards).unwrap().into_iter().collect::<Vec<_>>(),
+        [(1, 1), (2, 2), (3, 4), (4, 8), (5, 14), (6, 1)]
+    );
+    assert_eq!(
+        cascade(&cards[..3]).unwrap_err(),
+        CardError::WinsPastLastCard {
+            id: 1,
+            wins: 4,
+            last: 3
+        }
+    );
+    let cards = TEST_CASE
+        .lines()
+        .skip(4)
+        .chain(["Card 8: 1 | 2"])
+        .map(Scratchcard::from)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        cascade(&cards).unwrap_err(),
+        CardError::NonContiguousId {
+            expected: 7,
+            found: 8
+        }
+    );
+}
+
+fn process_p1(data: &str) -> usize {
+    data.lines()
+        .map(|line| Scratchcard::from(line).points())
+        .sum()
 }
 
 #[test]
@@ -49,41 +149,8 @@ fn test_process_p1() {
 }
 
 fn process_p2(data: &str) -> usize {
-    let nlines = data.lines().count();
-    let mut copies = vec![0usize; nlines];
-    let mut result = 0usize;
-    for (i, line) in data.lines().enumerate() {
-        result += copies[i] + 1;
-        let (win_nums, scratched_nums) = line
-            .split_once(':')
-            .expect("':' not  in line")
-            .1
-            .split_once('|')
-            .expect("'|' is missing");
-        let win_nums = win_nums
-            .split(' ')
-            .filter_map(|s| {
-                if s.is_empty() {
-                    return None;
-                }
-                Some(s.parse::<usize>().expect("not a valid number"))
-            })
-            .collect::<HashSet<_>>();
-        let wins = scratched_nums
-            .split(' ')
-            .filter_map(|s| {
-                if s.is_empty() {
-                    return None;
-                }
-                Some(s.parse::<usize>().expect("not a valid number"))
-            })
-            .filter(|num| win_nums.contains(num))
-            .count();
-        for j in (i + 1)..(i + 1 + wins) {
-            copies[j] += copies[i] + 1
-        }
-    }
-    result
+    let cards = data.lines().map(Scratchcard::from).collect::<Vec<_>>();
+    cascade(&cards).expect("Ill defined cards").values().sum()
 }
 
 #[test]
@@ -1,4 +1,5 @@
-use std::time::Instant;
+use intervals::{Interval, IntervalSet, PiecewiseLinear};
+use std::{collections::HashMap, time::Instant};
 
 #[cfg(test)]
 const TEST_CASE: &str = "seeds: 79 14 55 13
@@ -41,168 +42,209 @@ fn parse_line_to_usize(line: &str) -> Vec<usize> {
         .collect()
 }
 
-fn get_ranges<'a, I: Iterator<Item = &'a str>>(lines: &mut I) -> Vec<[usize; 3]> {
-    lines
-        .skip(1)
-        .take_while(|line| !line.is_empty())
-        .map(|line| {
-            let nums = parse_line_to_usize(line);
-            [nums[1], nums[0], nums[2]]
-        })
-        .collect()
+/// One `source-to-target map:` block of the almanac.
+#[derive(Debug)]
+struct Stage<'a> {
+    source: &'a str,
+    target: &'a str,
+    map: PiecewiseLinear,
 }
 
-fn process_p1(data: &str) -> usize {
-    let mut lines = data.lines();
-    let initial_values = lines
-        .next()
-        .as_ref()
-        .and_then(|line| line.split_once("seeds: "))
-        .map(|(_, values)| parse_line_to_usize(values))
-        .expect("Ill defined seeds"); // process initial values
-    lines.next(); // skip a line
-    (0..7)
-        .fold(initial_values, |nums, _| {
-            let ranges = get_ranges(&mut lines);
-            nums.into_iter()
-                .map(|i| {
-                    ranges
-                        .iter()
-                        .find_map(|&[s_start, t_start, length]| {
-                            if i >= s_start && i < s_start + length {
-                                Some(t_start + (i - s_start))
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or(i)
+impl<'a> Stage<'a> {
+    fn parse<I: Iterator<Item = &'a str>>(header: &'a str, lines: &mut I) -> Self {
+        let (source, target) = header
+            .strip_suffix(" map:")
+            .and_then(|name| name.split_once("-to-"))
+            .expect("Ill defined map header");
+        let map = PiecewiseLinear::new(
+            lines
+                .take_while(|line| !line.is_empty())
+                .map(|line| {
+                    let nums = parse_line_to_usize(line);
+                    (Interval::with_len(nums[1], nums[2]), nums[0])
                 })
-                .collect()
-        })
-        .into_iter()
-        .min()
-        .expect("No seed number")
+                .collect(),
+        );
+        Self {
+            source,
+            target,
+            map,
+        }
+    }
 }
 
-#[test]
-fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE), 35)
+#[derive(Debug)]
+struct Almanac<'a> {
+    seeds: Vec<usize>,
+    /// Stages chained from `seed` to the last category.
+    stages: Vec<Stage<'a>>,
 }
 
-#[derive(Debug, PartialEq)]
-struct MappedRange {
-    mapped: Option<(usize, usize)>,
-    unmapped: Vec<(usize, usize)>,
+impl<'a> From<&'a str> for Almanac<'a> {
+    fn from(data: &'a str) -> Self {
+        let mut lines = data.lines();
+        let seeds = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seeds:"))
+            .map(parse_line_to_usize)
+            .expect("Ill defined seeds");
+        let mut stages = HashMap::new();
+        while let Some(header) = lines.find(|line| !line.is_empty()) {
+            let stage = Stage::parse(header, &mut lines);
+            assert!(
+                stages.insert(stage.source, stage).is_none(),
+                "Several maps from the same category"
+            );
+        }
+        let mut category = "seed";
+        let mut chained = vec![];
+        while let Some(stage) = stages.remove(category) {
+            category = stage.target;
+            chained.push(stage);
+        }
+        assert!(stages.is_empty(), "Maps not reachable from seeds");
+        Self {
+            seeds,
+            stages: chained,
+        }
+    }
 }
 
-fn map_range(start: usize, length: usize, [s_start, t_start, mstep]: [usize; 3]) -> MappedRange {
-    if (start >= s_start + mstep) || (start + length <= s_start) {
-        return MappedRange {
-            unmapped: vec![(start, length)],
-            mapped: None,
-        };
+impl<'a> Almanac<'a> {
+    fn seed_ranges(&self) -> IntervalSet {
+        self.seeds
+            .chunks(2)
+            .map(|nums| Interval::with_len(nums[0], nums[1]))
+            .collect()
     }
-    if (start >= s_start) && (start + length <= s_start + mstep) {
-        return MappedRange {
-            unmapped: vec![],
-            mapped: Some((t_start + (start - s_start), length)),
-        };
+
+    /// Push the seed ranges through each stage, merging overlapping and
+    /// adjacent ranges in between. Returns the ranges reached in each
+    /// category, starting with the seeds.
+    fn propagate(&self) -> Vec<(&'a str, IntervalSet)> {
+        let seeds = ("seed", self.seed_ranges());
+        let mut reached = vec![seeds];
+        for stage in &self.stages {
+            let ranges = stage.map.apply_set(&reached.last().unwrap().1);
+            reached.push((stage.target, ranges));
+        }
+        reached
     }
-    if (start < s_start) && (start + length > s_start + mstep) {
-        return MappedRange {
-            unmapped: vec![
-                (start, s_start - start),
-                (t_start + mstep, start + length - s_start - mstep),
-            ],
-            mapped: Some((t_start, mstep)),
-        };
+
+    /// The category reached by the last stage.
+    fn target(&self) -> &'a str {
+        self.stages.last().map_or("seed", |stage| stage.target)
     }
-    if start < s_start {
-        return MappedRange {
-            unmapped: vec![(start, s_start - start)],
-            mapped: Some((t_start, start + length - s_start)),
-        };
+
+    /// All the stages composed into a single map from seed to target.
+    fn composed(&self) -> PiecewiseLinear {
+        self.stages
+            .iter()
+            .fold(PiecewiseLinear::default(), |map, stage| {
+                map.compose(&stage.map)
+            })
     }
-    MappedRange {
-        unmapped: vec![(s_start + mstep, start + length - s_start - mstep)],
-        mapped: Some((t_start + (start - s_start), mstep + s_start - start)),
+
+    /// The seeds mapped onto a given value of the target category.
+    fn seeds_for(&self, value: usize) -> IntervalSet {
+        self.composed().preimage(Interval::with_len(value, 1))
     }
 }
 
 #[test]
-fn test_map_range() {
-    assert_eq!(
-        map_range(0, 5, [5, 5, 2]),
-        MappedRange {
-            unmapped: vec![(0, 5)],
-            mapped: None
-        }
-    );
+fn test_almanac() {
+    let almanac = Almanac::from(TEST_CASE);
+    assert_eq!(almanac.target(), "location");
+    assert_eq!(almanac.stages[2].source, "fertilizer");
+    let composed = almanac.composed();
     assert_eq!(
-        map_range(0, 6, [5, 5, 2]),
-        MappedRange {
-            unmapped: vec![(0, 5)],
-            mapped: Some((5, 1))
-        }
+        almanac
+            .seeds
+            .iter()
+            .map(|&s| composed.apply(s))
+            .collect::<Vec<_>>(),
+        [82, 43, 86, 35]
     );
-    assert_eq!(
-        map_range(0, 10, [5, 5, 2]),
-        MappedRange {
-            unmapped: vec![(0, 5), (7, 3)],
-            mapped: Some((5, 2))
-        }
+    assert!(almanac.seeds_for(46).contains(82));
+    assert!(almanac.seeds_for(35).contains(13));
+    for seed in almanac.seeds_for(46).iter().flat_map(|i| i.start..i.end) {
+        assert_eq!(composed.apply(seed), 46);
+    }
+}
+
+#[test]
+fn test_almanac_shuffled_stages() {
+    let (seeds, maps) = TEST_CASE.split_once("\n\n").unwrap();
+    let shuffled = format!(
+        "{seeds}\n\n{}",
+        maps.rsplit("\n\n").collect::<Vec<_>>().join("\n\n")
     );
-    assert_eq!(
-        map_range(6, 4, [5, 5, 2]),
-        MappedRange {
-            unmapped: vec![(7, 3)],
-            mapped: Some((6, 1))
-        }
-    )
+    let almanac = Almanac::from(shuffled.as_str());
+    assert_eq!(almanac.stages[0].source, "seed");
+    assert_eq!(almanac.target(), "location");
+    assert_eq!(process_p1(&shuffled), 35);
+    assert_eq!(process_p2(&shuffled), 46);
+}
+
+fn process_p1(data: &str) -> usize {
+    let almanac = Almanac::from(data);
+    let map = almanac.composed();
+    almanac
+        .seeds
+        .iter()
+        .map(|&seed| map.apply(seed))
+        .min()
+        .expect("No seed number")
+}
+
+#[test]
+fn test_process_p1() {
+    assert_eq!(process_p1(TEST_CASE), 35)
 }
 
 fn process_p2(data: &str) -> usize {
-    let mut lines = data.lines();
-    let initial_values = lines
-        .next()
-        .as_ref()
-        .and_then(|line| line.split_once("seeds: "))
-        .map(|(_, values)| {
-            parse_line_to_usize(values)
-                .chunks(2)
-                .map(|nums| (nums[0], nums[1]))
-                .collect::<Vec<_>>()
-        })
-        .expect("Ill defined seeds");
-    lines.next(); //skip a line
-    (0..7)
-        .fold(initial_values, |nums, _| {
-            let map = get_ranges(&mut lines);
-            nums.into_iter()
-                .fold(vec![], |mut result, (start, length)| {
-                    let mut remaining = vec![(start, length)];
-                    for &range in &map {
-                        remaining =
-                            remaining
-                                .into_iter()
-                                .fold(vec![], |mut acc, (start, length)| {
-                                    let MappedRange { mapped, unmapped } =
-                                        map_range(start, length, range);
-                                    if let Some(mapped_value) = mapped {
-                                        result.push(mapped_value);
-                                    }
-                                    acc.extend(unmapped);
-                                    acc
-                                });
-                    }
-                    result.extend(remaining);
-                    result
-                })
-        })
+    Almanac::from(data)
+        .propagate()
+        .last()
+        .and_then(|(_, ranges)| ranges.min())
+        .expect("No seed number")
+}
+
+/// Number of disjoint ranges reached in each category.
+fn stage_stats(data: &str) -> Vec<(&str, usize)> {
+    Almanac::from(data)
+        .propagate()
         .into_iter()
-        .map(|(i, _)| i)
-        .min()
-        .unwrap()
+        .map(|(category, ranges)| (category, ranges.intervals().len()))
+        .collect()
+}
+
+#[test]
+fn test_stage_stats() {
+    assert_eq!(
+        stage_stats(TEST_CASE),
+        [
+            ("seed", 2),
+            ("soil", 2),
+            ("fertilizer", 2),
+            ("water", 3),
+            ("light", 3),
+            ("temperature", 4),
+            ("humidity", 4),
+            ("location", 4),
+        ]
+    )
+}
+
+#[test]
+fn test_overlapping_seeds() {
+    // The same seeds given several times, overlapping or adjacent, are
+    // merged into a single range and lead to the same result.
+    let (_, maps) = TEST_CASE.split_once("\n").unwrap();
+    let data = format!("seeds: 79 14 79 14 80 5 90 3 55 13{maps}");
+    assert_eq!(process_p2(&data), 46);
+    assert_eq!(stage_stats(&data)[0], ("seed", 2));
+    assert_eq!(stage_stats(&data), stage_stats(TEST_CASE));
 }
 
 #[test]
@@ -219,4 +261,17 @@ fn main() {
     let result_p2 = process_p2(&data);
     let t2 = Instant::now();
     println!("The result of p2 is {}. ({:?})", result_p2, t2 - t1);
+    for (category, ranges) in stage_stats(&data) {
+        println!("{} ranges of {}.", ranges, category);
+    }
+    let almanac = Almanac::from(data.as_str());
+    let seeds = almanac
+        .seeds_for(result_p2)
+        .intersection(&almanac.seed_ranges());
+    println!(
+        "The {} {} is reached from seeds {:?}.",
+        almanac.target(),
+        result_p2,
+        seeds.intervals()
+    );
 }
@@ -4,22 +4,56 @@ use std::time::Instant;
 const TEST_CASE: &str = "Time:      7  15   30
 Distance:  9  40  200";
 
+/// Hold times `[lo, hi]` for which the boat beats the record, or `None`
+/// if the race cannot be won.
+fn winning_interval(t_race: usize, d_record: usize) -> Option<(usize, usize)> {
+    let (t, d) = (t_race as u128, d_record as u128);
+    let beats = |h: u128| h * (t - h) > d;
+    // The distance h * (t - h) is above the record strictly between the
+    // roots (t ± sqrt(t² - 4d)) / 2.
+    let discriminant = (t * t).checked_sub(4 * d)?;
+    let mut lo = (t - discriminant.isqrt()) / 2;
+    while lo > 0 && beats(lo - 1) {
+        lo -= 1;
+    }
+    while lo <= t / 2 && !beats(lo) {
+        lo += 1;
+    }
+    if lo > t / 2 {
+        return None;
+    }
+    Some((lo as usize, (t - lo) as usize))
+}
+
 fn winable_config(t_race: usize, d_record: usize) -> usize {
-    let x = (t_race as f64) / 2.0;
-    let mut x = (x - (x.powi(2) - d_record as f64).sqrt()) as usize;
-    if x * (t_race - x) > d_record {
-        loop {
-            x -= 1;
-            if x * (t_race - x) <= d_record {
-                return t_race - 2 * x - 1;
-            }
-        }
+    winning_interval(t_race, d_record).map_or(0, |(lo, hi)| hi - lo + 1)
+}
+
+#[test]
+fn test_winning_interval() {
+    assert_eq!(winning_interval(7, 9), Some((2, 5)));
+    assert_eq!(winning_interval(30, 200), Some((11, 19)));
+    assert_eq!(winning_interval(4, 4), None);
+    assert_eq!(winning_interval(4, 3), Some((2, 2)));
+    assert_eq!(winning_interval(0, 0), None);
+    assert_eq!(winning_interval(1, 0), None);
+    assert_eq!(winning_interval(2, 0), Some((1, 1)));
+    assert_eq!(winning_interval(10, 1000), None);
+    // Records sitting exactly on a root, too large for an f64 square root.
+    let t = 4_000_000_007usize;
+    for lo in [1, 123_456_789, t / 2 - 1] {
+        assert_eq!(
+            winning_interval(t, lo * (t - lo)),
+            Some((lo + 1, t - lo - 1))
+        );
+        assert_eq!(winning_interval(t, lo * (t - lo) - 1), Some((lo, t - lo)));
     }
-    loop {
-        x += 1;
-        if x * (t_race - x) > d_record {
-            return t_race - 2 * (x - 1) - 1;
-        };
+    for t in 0..40 {
+        for d in 0..450 {
+            let brute = (0..=t).filter(|h| h * (t - h) > d).collect::<Vec<_>>();
+            let expected = brute.first().map(|&lo| (lo, *brute.last().unwrap()));
+            assert_eq!(winning_interval(t, d), expected);
+        }
     }
 }
 
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, collections::HashMap, time::Instant};
+use std::time::Instant;
 
 #[cfg(test)]
 const TEST_CASE: &str = "32T3K 765
@@ -7,124 +7,215 @@ KK677 28
 KTJJT 220
 QQQJA 483";
 
-const CARDS_P1: [char; 13] = [
-    '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A',
-];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
 
-fn count_occurences_p1(hand: &str) -> HashMap<char, usize> {
-    let mut counts = HashMap::from_iter(CARDS_P1.iter().map(|c| (*c, 0)));
-    hand.chars().for_each(|c| {
-        *counts.get_mut(&c).unwrap() += 1;
-    });
-    counts
+impl HandType {
+    /// Classify a hand from its group sizes, largest first. Hands of more
+    /// than five cards are classified by their two largest groups.
+    fn from_groups(groups: &[usize]) -> Self {
+        match (groups.first().copied(), groups.get(1).copied()) {
+            (Some(5..), _) => Self::FiveOfAKind,
+            (Some(4), _) => Self::FourOfAKind,
+            (Some(3), Some(2..)) => Self::FullHouse,
+            (Some(3), _) => Self::ThreeOfAKind,
+            (Some(2), Some(2..)) => Self::TwoPair,
+            (Some(2), _) => Self::OnePair,
+            _ => Self::HighCard,
+        }
+    }
 }
 
-fn val_from_counts(counts: &HashMap<char, usize>) -> usize {
-    counts
-        .values()
-        .filter(|&&v| v != 0)
-        .map(|&v| 10usize.pow(v as u32 - 1))
-        .sum()
+/// How hands are read and compared.
+#[derive(Debug, Clone)]
+struct Rules {
+    /// Cards from the weakest to the strongest.
+    order: Vec<char>,
+    /// Cards standing for whichever card makes the hand strongest.
+    wildcards: Vec<char>,
+    hand_size: usize,
 }
 
-fn process_p1(data: &str) -> usize {
-    let map_val: HashMap<char, usize> =
-        HashMap::from_iter(CARDS_P1.iter().enumerate().map(|(i, c)| (*c, i + 1)));
-    let mut hands_and_bids = data
-        .lines()
-        .map(|line| {
-            let (hand, bid) = line.split_once(' ').unwrap();
-            (hand, bid.parse::<usize>().unwrap())
-        })
-        .collect::<Vec<_>>();
-    hands_and_bids.sort_by(|(h1, _), (h2, _)| {
-        let val1 = val_from_counts(&count_occurences_p1(h1));
-        let val2 = val_from_counts(&count_occurences_p1(h2));
-        match val1.cmp(&val2) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-            Ordering::Equal => h1
-                .chars()
-                .zip(h2.chars())
-                .find_map(|(c1, c2)| {
-                    let n1 = map_val.get(&c1).unwrap();
-                    let n2 = map_val.get(&c2).unwrap();
-                    if n1 == n2 {
-                        None
-                    } else {
-                        Some(n1.cmp(n2))
-                    }
-                })
-                .unwrap_or(Ordering::Equal),
+impl Rules {
+    fn standard() -> Self {
+        Self {
+            order: "23456789TJQKA".chars().collect(),
+            wildcards: vec![],
+            hand_size: 5,
         }
-    });
-    hands_and_bids
-        .into_iter()
-        .enumerate()
-        .map(|(i, (_, bid))| bid * (i + 1))
-        .sum()
+    }
+
+    fn jokers() -> Self {
+        Self {
+            order: "J23456789TQKA".chars().collect(),
+            wildcards: vec!['J'],
+            hand_size: 5,
+        }
+    }
 }
 
-#[test]
-fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE), 6440)
+#[derive(Debug)]
+struct Hand<'a> {
+    cards: &'a str,
+    bid: usize,
+    kind: HandType,
+    /// The type, then the rank of each card in order.
+    key: (HandType, Vec<usize>),
 }
 
-const CARDS_P2: [char; 13] = [
-    'J', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'Q', 'K', 'A',
-];
-
-fn count_occurences_p2(hand: &str) -> HashMap<char, usize> {
-    let mut counts = HashMap::from_iter(CARDS_P2.iter().map(|c| (*c, 0)));
-    hand.chars().for_each(|c| {
-        *counts.get_mut(&c).unwrap() += 1;
-    });
-    let jcount = counts.remove(&'J').unwrap();
-    if jcount > 0 {
-        let maxkey = *counts.iter().max_by_key(|(_, v)| *v).unwrap().0;
-        *counts.get_mut(&maxkey).unwrap() += jcount
+impl<'a> Hand<'a> {
+    fn new(cards: &'a str, bid: usize, rules: &Rules) -> Self {
+        let ranks = cards
+            .chars()
+            .map(|c| {
+                rules
+                    .order
+                    .iter()
+                    .position(|&o| o == c)
+                    .expect("Unknown card")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ranks.len(), rules.hand_size, "Wrong hand size");
+        let mut counts = vec![0usize; rules.order.len()];
+        let mut wildcards = 0;
+        for c in cards.chars() {
+            if rules.wildcards.contains(&c) {
+                wildcards += 1;
+            } else {
+                counts[rules.order.iter().position(|&o| o == c).unwrap()] += 1;
+            }
+        }
+        counts.retain(|&n| n > 0);
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        match counts.first_mut() {
+            Some(largest) => *largest += wildcards,
+            None => counts.push(wildcards),
+        }
+        let kind = HandType::from_groups(&counts);
+        Self {
+            cards,
+            bid,
+            kind,
+            key: (kind, ranks),
+        }
     }
-    counts
 }
 
-fn process_p2(data: &str) -> usize {
-    let map_val: HashMap<char, usize> =
-        HashMap::from_iter(CARDS_P2.iter().enumerate().map(|(i, c)| (*c, i + 1)));
-    let mut hands_and_bids = data
+/// Hands sorted from the weakest to the strongest.
+fn rank_hands<'a>(data: &'a str, rules: &Rules) -> Vec<Hand<'a>> {
+    let mut hands = data
         .lines()
         .map(|line| {
-            let (hand, bid) = line.split_once(' ').unwrap();
-            (hand, bid.parse::<usize>().unwrap())
+            let (cards, bid) = line.split_once(' ').unwrap();
+            Hand::new(cards, bid.parse::<usize>().unwrap(), rules)
         })
         .collect::<Vec<_>>();
-    hands_and_bids.sort_by(|(h1, _), (h2, _)| {
-        let val1 = val_from_counts(&count_occurences_p2(h1));
-        let val2 = val_from_counts(&count_occurences_p2(h2));
-        match val1.cmp(&val2) {
-            Ordering::Less => Ordering::Less,
-            Ordering::Greater => Ordering::Greater,
-            Ordering::Equal => h1
-                .chars()
-                .zip(h2.chars())
-                .find_map(|(c1, c2)| {
-                    let n1 = map_val.get(&c1).unwrap();
-                    let n2 = map_val.get(&c2).unwrap();
-                    if n1 == n2 {
-                        None
-                    } else {
-                        Some(n1.cmp(n2))
-                    }
-                })
-                .unwrap_or(Ordering::Equal),
-        }
-    });
-    hands_and_bids
+    hands.sort_unstable_by(|h1, h2| h1.key.cmp(&h2.key));
+    hands
+}
+
+fn total_winnings(data: &str, rules: &Rules) -> usize {
+    rank_hands(data, rules)
         .into_iter()
         .enumerate()
-        .map(|(i, (_, bid))| bid * (i + 1))
+        .map(|(i, hand)| hand.bid * (i + 1))
         .sum()
 }
 
+#[test]
+fn test_rank_hands() {
+    let kinds = |rules| {
+        rank_hands(TEST_CASE, &rules)
+            .into_iter()
+            .map(|hand| (hand.cards, hand.kind))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        kinds(Rules::standard()),
+        [
+            ("32T3K", HandType::OnePair),
+            ("KTJJT", HandType::TwoPair),
+            ("KK677", HandType::TwoPair),
+            ("T55J5", HandType::ThreeOfAKind),
+            ("QQQJA", HandType::ThreeOfAKind),
+        ]
+    );
+    assert_eq!(
+        kinds(Rules::jokers()),
+        [
+            ("32T3K", HandType::OnePair),
+            ("KK677", HandType::TwoPair),
+            ("T55J5", HandType::FourOfAKind),
+            ("QQQJA", HandType::FourOfAKind),
+            ("KTJJT", HandType::FourOfAKind),
+        ]
+    );
+    let jokers = Rules::jokers();
+    assert_eq!(Hand::new("JJJJJ", 0, &jokers).kind, HandType::FiveOfAKind);
+    assert_eq!(Hand::new("2233J", 0, &jokers).kind, HandType::FullHouse);
+    assert_eq!(Hand::new("2345J", 0, &jokers).kind, HandType::OnePair);
+}
+
+#[test]
+fn test_custom_rules() {
+    let rules = Rules {
+        order: "abc*".chars().collect(),
+        wildcards: vec!['*'],
+        hand_size: 3,
+    };
+    let hands = rank_hands("abc 1\nc*a 2\n*** 3\nccc 4\naab 5", &rules);
+    assert_eq!(
+        hands
+            .iter()
+            .map(|hand| (hand.cards, hand.kind))
+            .collect::<Vec<_>>(),
+        [
+            ("abc", HandType::HighCard),
+            ("aab", HandType::OnePair),
+            ("c*a", HandType::OnePair),
+            ("ccc", HandType::ThreeOfAKind),
+            ("***", HandType::ThreeOfAKind),
+        ]
+    );
+    // Hands of the same type tie-break on their cards, whatever their groups.
+    let rules = Rules {
+        hand_size: 6,
+        ..Rules::standard()
+    };
+    let hands = rank_hands("222333 1\nAAAKKQ 2", &rules);
+    assert_eq!(
+        hands
+            .iter()
+            .map(|hand| (hand.cards, hand.kind))
+            .collect::<Vec<_>>(),
+        [
+            ("222333", HandType::FullHouse),
+            ("AAAKKQ", HandType::FullHouse)
+        ]
+    );
+}
+
+fn process_p1(data: &str) -> usize {
+    total_winnings(data, &Rules::standard())
+}
+
+#[test]
+fn test_process_p1() {
+    assert_eq!(process_p1(TEST_CASE), 6440)
+}
+
+fn process_p2(data: &str) -> usize {
+    total_winnings(data, &Rules::jokers())
+}
+
 #[test]
 fn test_process_p2() {
     assert_eq!(process_p2(TEST_CASE), 5905)
@@ -139,4 +230,11 @@ fn main() {
     let result_p2 = process_p2(&data);
     let t2 = Instant::now();
     println!("The result of p2 is {}. ({:?})", result_p2, t2 - t1);
+    for (part, rules) in [("p1", Rules::standard()), ("p2", Rules::jokers())] {
+        let hand = rank_hands(&data, &rules).pop().unwrap();
+        println!(
+            "The strongest hand of {} is {} ({:?}).",
+            part, hand.cards, hand.kind
+        );
+    }
 }
@@ -1,4 +1,5 @@
-use std::{collections::HashMap, time::Instant};
+use num::integer::{ExtendedGcd, Integer};
+use std::{collections::HashMap, fmt, time::Instant};
 
 #[cfg(test)]
 const TEST_CASE_1: &str = "RL
@@ -23,73 +24,253 @@ const TEST_CASE_2: &str = "LR
 22Z = (22B, 22B)
 XXX = (XXX, XXX)";
 
-fn get_graph<'a, L: Iterator<Item = &'a str>>(lines: L) -> HashMap<&'a str, (&'a str, &'a str)> {
-    lines
-        .map(|line| {
-            let source = &line[..3];
-            let target_left = &line[7..10];
-            let target_right = &line[12..15];
-            (source, (target_left, target_right))
+/// The network, with node names interned to indices.
+#[derive(Debug)]
+struct Network<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, usize>,
+    /// Left and right neighbours of each node.
+    edges: Vec<(usize, usize)>,
+}
+
+impl<'a> Network<'a> {
+    fn intern(&mut self, name: &'a str) -> usize {
+        *self.ids.entry(name).or_insert_with(|| {
+            self.names.push(name);
+            self.names.len() - 1
+        })
+    }
+
+    /// Parse `NAME = (LEFT, RIGHT)` lines.
+    fn parse<L: Iterator<Item = &'a str>>(lines: L) -> Self {
+        let mut network = Self {
+            names: vec![],
+            ids: HashMap::new(),
+            edges: vec![],
+        };
+        let mut edges = HashMap::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let (source, targets) = line.split_once('=').expect("'=' not in line");
+            let (left, right) = targets
+                .trim()
+                .strip_prefix('(')
+                .and_then(|targets| targets.strip_suffix(')'))
+                .and_then(|targets| targets.split_once(','))
+                .expect("Ill defined targets");
+            let source = network.intern(source.trim());
+            let targets = (network.intern(left.trim()), network.intern(right.trim()));
+            assert!(
+                edges.insert(source, targets).is_none(),
+                "Node defined twice"
+            );
+        }
+        network.edges = (0..network.names.len())
+            .map(|id| {
+                *edges
+                    .get(&id)
+                    .unwrap_or_else(|| panic!("Undefined node {}", network.names[id]))
+            })
+            .collect();
+        network
+    }
+
+    fn nodes(&self, predicate: impl Fn(&str) -> bool) -> Vec<usize> {
+        (0..self.names.len())
+            .filter(|&id| predicate(self.names[id]))
+            .collect()
+    }
+}
+
+#[test]
+fn test_network() {
+    let network = Network::parse(
+        "start = (a1, finish)
+a1   = (finish,start)
+finish = (finish, finish)"
+            .lines(),
+    );
+    assert_eq!(network.names, ["start", "a1", "finish"]);
+    assert_eq!(network.edges, [(1, 2), (2, 0), (2, 2)]);
+    assert_eq!(network.nodes(|n| n.len() > 2), [0, 2]);
+}
+
+fn parse_instructions(line: &str) -> Vec<bool> {
+    line.chars()
+        .map(|c| match c {
+            'R' => true,
+            'L' => false,
+            _ => panic!("not supposed to append"),
         })
         .collect()
 }
 
-fn count_to_exit_p1(
-    curnode: &str,
-    count: usize,
-    graph: &HashMap<&str, (&str, &str)>,
-    mut instructions: impl Iterator<Item = char>,
-) -> usize {
-    if curnode == "ZZZ" {
-        return count;
+/// The steps at which a walk is on an exit node: a few transient hits
+/// before the walk enters its cycle, then hits repeating every `period`.
+#[derive(Debug, PartialEq)]
+struct Cycle {
+    offset: usize,
+    period: usize,
+    /// Every hit before `offset + period`.
+    hits: Vec<usize>,
+}
+
+impl Cycle {
+    fn periodic(&self) -> impl Iterator<Item = usize> + '_ {
+        self.hits.iter().copied().filter(|&h| h >= self.offset)
+    }
+
+    fn contains(&self, step: usize) -> bool {
+        if step < self.offset {
+            return self.hits.contains(&step);
+        }
+        self.periodic()
+            .any(|h| h % self.period == step % self.period)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+enum WalkError {
+    NoStart,
+    NoExit(String),
+    NoCommonExit,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoStart => write!(f, "no node is a start node"),
+            Self::NoExit(start) => write!(f, "no exit is reachable from {start}"),
+            Self::NoCommonExit => write!(f, "the walks never reach exits at the same step"),
+        }
     }
-    let targets = graph.get(curnode).unwrap();
-    match instructions.next().unwrap() {
-        'R' => count_to_exit_p1(targets.1, count + 1, graph, instructions),
-        'L' => count_to_exit_p1(targets.0, count + 1, graph, instructions),
-        _ => panic!("not supposed to append"),
+}
+
+impl std::error::Error for WalkError {}
+
+/// Walk from `start` until a (node, instruction) state repeats.
+fn analyse_walk(network: &Network, instructions: &[bool], start: usize, exits: &[bool]) -> Cycle {
+    let mut seen = HashMap::new();
+    let mut hits = vec![];
+    let mut node = start;
+    for step in 0.. {
+        let state = (node, step % instructions.len());
+        if let Some(&offset) = seen.get(&state) {
+            return Cycle {
+                offset,
+                period: step - offset,
+                hits,
+            };
+        }
+        seen.insert(state, step);
+        if exits[node] {
+            hits.push(step);
+        }
+        let (left, right) = network.edges[node];
+        node = if instructions[state.1] { right } else { left };
     }
+    unreachable!()
 }
 
-fn process_p1(data: &str) -> usize {
-    let mut lines = data.lines();
-    let instructions = lines.next().unwrap();
-    let graph = get_graph(lines.skip(1));
-    let (count, curnode) = (0, "AAA");
-    count_to_exit_p1(curnode, count, &graph, instructions.chars().cycle())
+/// Solve `t ≡ r1 (mod m1)` and `t ≡ r2 (mod m2)` for moduli that need not
+/// be coprime, returning `(r, lcm(m1, m2))`.
+fn crt((r1, m1): (i128, i128), (r2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let ExtendedGcd { gcd, x, .. } = m1.extended_gcd(&m2);
+    if (r2 - r1) % gcd != 0 {
+        return None;
+    }
+    let lcm = m1 / gcd * m2;
+    let k = ((r2 - r1) / gcd * x).rem_euclid(m2 / gcd);
+    Some(((r1 + m1 * k).rem_euclid(lcm), lcm))
 }
 
 #[test]
-fn test_process_p1() {
-    assert_eq!(process_p1(TEST_CASE_1), 2)
+fn test_crt() {
+    assert_eq!(crt((1, 2), (2, 3)), Some((5, 6)));
+    assert_eq!(crt((3, 4), (1, 6)), Some((7, 12)));
+    assert_eq!(crt((0, 4), (1, 6)), None);
 }
 
-fn count_to_exit_p2(
-    curnode: &str,
-    count: usize,
-    graph: &HashMap<&str, (&str, &str)>,
-    mut instructions: impl Iterator<Item = char>,
-) -> usize {
-    if curnode.ends_with('Z') {
-        return count;
+/// First step at which every walk is on an exit.
+fn common_exit(cycles: &[Cycle]) -> Result<usize, WalkError> {
+    let Some(latest) = cycles.iter().max_by_key(|c| c.offset) else {
+        return Err(WalkError::NoStart);
+    };
+    // Before the latest walk enters its cycle, only its transient hits
+    // are candidates.
+    if let Some(step) = latest
+        .hits
+        .iter()
+        .copied()
+        .filter(|&h| h < latest.offset)
+        .find(|&h| cycles.iter().all(|c| c.contains(h)))
+    {
+        return Ok(step);
     }
-    let targets = graph.get(curnode).unwrap();
-    match instructions.next().unwrap() {
-        'R' => count_to_exit_p2(targets.1, count + 1, graph, instructions),
-        'L' => count_to_exit_p2(targets.0, count + 1, graph, instructions),
-        _ => panic!("not supposed to append"),
+    // Afterwards, every walk is periodic: combine one residue per walk.
+    let mut solutions = vec![(0i128, 1i128)];
+    for cycle in cycles {
+        solutions = solutions
+            .into_iter()
+            .flat_map(|solution| {
+                cycle
+                    .periodic()
+                    .filter_map(move |h| crt(solution, (h as i128, cycle.period as i128)))
+            })
+            .collect();
     }
+    let offset = latest.offset as i128;
+    solutions
+        .into_iter()
+        .map(|(r, m)| r + ((offset - r).max(0) + m - 1) / m * m)
+        .min()
+        .map(|step| step as usize)
+        .ok_or(WalkError::NoCommonExit)
 }
 
-fn process_p2(data: &str) -> usize {
+/// First step at which the walks from every start node are all on an
+/// exit node.
+fn walk_all(
+    data: &str,
+    is_start: impl Fn(&str) -> bool,
+    is_exit: impl Fn(&str) -> bool,
+) -> Result<usize, WalkError> {
     let mut lines = data.lines();
-    let instructions = lines.next().unwrap();
-    let graph = get_graph(lines.skip(1));
-    graph
-        .keys()
-        .filter(|n| n.ends_with('A'))
-        .map(|n| count_to_exit_p2(n, 0, &graph, instructions.chars().cycle()))
-        .fold(1, num::integer::lcm)
+    let instructions = parse_instructions(lines.next().unwrap());
+    let network = Network::parse(lines);
+    let exits = network
+        .names
+        .iter()
+        .map(|name| is_exit(name))
+        .collect::<Vec<_>>();
+    let starts = network.nodes(is_start);
+    if starts.is_empty() {
+        return Err(WalkError::NoStart);
+    }
+    let cycles = starts
+        .into_iter()
+        .map(|start| {
+            let cycle = analyse_walk(&network, &instructions, start, &exits);
+            if cycle.hits.is_empty() {
+                return Err(WalkError::NoExit(network.names[start].to_string()));
+            }
+            Ok(cycle)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    common_exit(&cycles)
+}
+
+fn process_p1(data: &str) -> usize {
+    walk_all(data, |n| n == "AAA", |n| n == "ZZZ").expect("No walk from AAA to ZZZ")
+}
+
+#[test]
+fn test_process_p1() {
+    assert_eq!(process_p1(TEST_CASE_1), 2)
+}
+
+fn process_p2(data: &str) -> usize {
+    walk_all(data, |n| n.ends_with('A'), |n| n.ends_with('Z')).expect("No common exit")
 }
 
 #[test]
@@ -97,6 +278,110 @@ fn test_process_p2() {
     assert_eq!(process_p2(TEST_CASE_2), 6)
 }
 
+#[test]
+fn test_walk_all() {
+    // 11A reaches 11Z at odd steps, 22A reaches 22Z every third step: the
+    // exits meet at step 5, not at the lcm of the first hits.
+    let data = "L
+
+11A = (11Z, XXX)
+11Z = (11A, XXX)
+22A = (22B, XXX)
+22B = (22Z, XXX)
+22Z = (22A, XXX)
+XXX = (XXX, XXX)";
+    assert_eq!(
+        walk_all(data, |n| n.ends_with('A'), |n| n.ends_with('Z')),
+        Ok(5)
+    );
+    // A transient exit, never visited again.
+    let data = "L
+
+11A = (11Z, XXX)
+11Z = (11A, XXX)
+33A = (33Z, XXX)
+33Z = (33B, XXX)
+33B = (33B, XXX)
+XXX = (XXX, XXX)";
+    assert_eq!(
+        walk_all(data, |n| n.ends_with('A'), |n| n.ends_with('Z')),
+        Ok(1)
+    );
+    // Odd and even steps never meet.
+    let data = "L
+
+11A = (11Z, XXX)
+11Z = (11A, XXX)
+22A = (22B, XXX)
+22B = (22Z, XXX)
+22Z = (22B, XXX)
+XXX = (XXX, XXX)";
+    assert_eq!(
+        walk_all(data, |n| n.ends_with('A'), |n| n.ends_with('Z')),
+        Err(WalkError::NoCommonExit)
+    );
+    let data = "L
+
+11A = (11Z, XXX)
+11Z = (11A, XXX)
+44A = (XXX, XXX)
+XXX = (XXX, XXX)";
+    assert_eq!(
+        walk_all(data, |n| n.ends_with('A'), |n| n.ends_with('Z')),
+        Err(WalkError::NoExit("44A".to_string()))
+    );
+}
+
+#[test]
+fn test_analyse_walk() {
+    let mut lines = TEST_CASE_2.lines();
+    let instructions = parse_instructions(lines.next().unwrap());
+    let network = Network::parse(lines);
+    let exits = network
+        .names
+        .iter()
+        .map(|n| n.ends_with('Z'))
+        .collect::<Vec<_>>();
+    let cycle = analyse_walk(&network, &instructions, network.ids["22A"], &exits);
+    assert_eq!(
+        cycle,
+        Cycle {
+            offset: 1,
+            period: 6,
+            hits: vec![3, 6]
+        }
+    );
+    assert!(cycle.contains(9) && !cycle.contains(10));
+}
+
+#[test]
+fn test_walk_all_names() {
+    let data = "LR
+
+entrance = (hall, wall)
+hall = (wall, exit)
+wall = (wall, wall)
+exit = (exit, exit)
+side-door = (exit, wall)";
+    assert_eq!(walk_all(data, |n| n == "entrance", |n| n == "exit"), Ok(2));
+    assert_eq!(
+        walk_all(
+            data,
+            |n| n == "entrance" || n == "side-door",
+            |n| n == "exit"
+        ),
+        Ok(2)
+    );
+    assert_eq!(
+        walk_all(data, |n| n == "wall", |n| n == "exit"),
+        Err(WalkError::NoExit("wall".to_string()))
+    );
+    assert_eq!(
+        walk_all(data, |n| n == "AAA", |n| n == "exit"),
+        Err(WalkError::NoStart)
+    );
+}
+
 fn main() {
     let data = std::fs::read_to_string("data/day8.txt").unwrap();
     let t0 = Instant::now();
@@ -1,3 +1,6 @@
+mod sequence;
+
+use sequence::Sequence;
 use std::time::Instant;
 
 #[cfg(test)]
@@ -5,28 +8,19 @@ const TEST_CASE: &str = "0 3 6 9 12 15
 1 3 6 10 15 21
 10 13 16 21 30 45";
 
-fn process_p1(data: &str) -> i64 {
-    data.lines()
-        .map(|line| {
-            let mut nums = vec![line
-                .split_whitespace()
-                .map(|n| n.parse::<i64>().unwrap())
-                .collect::<Vec<_>>()];
-            while nums.last().unwrap().iter().any(|n| n != &0) {
-                nums.push(
-                    nums.last()
-                        .unwrap()
-                        .windows(2)
-                        .map(|slice| slice[1] - slice[0])
-                        .collect(),
-                )
-            }
-            nums.iter()
-                .rev()
-                .skip(1)
-                .map(|ns| ns.last().unwrap())
-                .sum::<i64>()
-        })
+fn parse_sequences(data: &str) -> impl Iterator<Item = Sequence> + '_ {
+    data.lines().map(|line| {
+        let values = line
+            .split_whitespace()
+            .map(|n| n.parse::<i128>().unwrap())
+            .collect::<Vec<_>>();
+        Sequence::new(&values).expect("Overflow")
+    })
+}
+
+fn process_p1(data: &str) -> i128 {
+    parse_sequences(data)
+        .map(|sequence| sequence.next(1).expect("Overflow"))
         .sum()
 }
 
@@ -35,24 +29,9 @@ fn test_process_p1() {
     assert_eq!(process_p1(TEST_CASE), 114)
 }
 
-fn process_p2(data: &str) -> i64 {
-    data.lines()
-        .map(|line| {
-            let mut nums = vec![line
-                .split_whitespace()
-                .map(|n| n.parse::<i64>().unwrap())
-                .collect::<Vec<_>>()];
-            while nums.last().unwrap().iter().any(|n| n != &0) {
-                nums.push(
-                    nums.last()
-                        .unwrap()
-                        .windows(2)
-                        .map(|slice| slice[1] - slice[0])
-                        .collect(),
-                )
-            }
-            nums.iter().rev().skip(1).fold(0, |acc, ns| ns[0] - acc)
-        })
+fn process_p2(data: &str) -> i128 {
+    parse_sequences(data)
+        .map(|sequence| sequence.previous(1).expect("Overflow"))
         .sum()
 }
 
@@ -70,4 +49,6 @@ fn main() {
     let result_p2 = process_p2(&data);
     let t2 = Instant::now();
     println!("The result of p2 is {}. ({:?})", result_p2, t2 - t1);
+    let max_degree = parse_sequences(&data).map(|s| s.degree()).max();
+    println!("The highest degree is {}.", max_degree.unwrap_or_default());
 }
@@ -0,0 +1,112 @@
+/// A sequence sampled at `x = 0, 1, ..., len - 1`, stored as the leading
+/// differences of its Newton forward expansion
+/// `y(x) = sum_k C(x, k) * Δ^k y(0)`, so that it can be evaluated at any
+/// `x` without building the difference pyramid.
+#[derive(Debug, PartialEq)]
+pub struct Sequence {
+    len: usize,
+    differences: Vec<i128>,
+}
+
+/// Binomial coefficient `C(x, k)` for any integer `x`, or `None` on overflow.
+fn binomial(x: i128, k: usize) -> Option<i128> {
+    (1..=k as i128).try_fold(1i128, |c, i| Some(c.checked_mul(x - i + 1)? / i))
+}
+
+impl Sequence {
+    /// Returns `None` if the differences overflow.
+    pub fn new(values: &[i128]) -> Option<Self> {
+        let mut differences = (0..values.len())
+            .map(|k| {
+                values[..=k]
+                    .iter()
+                    .enumerate()
+                    .try_fold(0i128, |acc, (j, &y)| {
+                        let term = binomial(k as i128, j)?.checked_mul(y)?;
+                        if (k - j) % 2 == 0 {
+                            acc.checked_add(term)
+                        } else {
+                            acc.checked_sub(term)
+                        }
+                    })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        while differences.last() == Some(&0) {
+            differences.pop();
+        }
+        Some(Self {
+            len: values.len(),
+            differences,
+        })
+    }
+
+    /// Degree of the lowest-degree polynomial going through every value.
+    pub fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    pub fn value_at(&self, x: i128) -> Option<i128> {
+        self.differences
+            .iter()
+            .enumerate()
+            .try_fold(0i128, |acc, (k, &d)| {
+                acc.checked_add(binomial(x, k)?.checked_mul(d)?)
+            })
+    }
+
+    /// Value `steps` after the last one.
+    pub fn next(&self, steps: usize) -> Option<i128> {
+        self.value_at(self.len as i128 - 1 + steps as i128)
+    }
+
+    /// Value `steps` before the first one.
+    pub fn previous(&self, steps: usize) -> Option<i128> {
+        self.value_at(-(steps as i128))
+    }
+}
+
+#[test]
+fn test_binomial() {
+    assert_eq!(binomial(5, 2), Some(10));
+    assert_eq!(binomial(2, 5), Some(0));
+    assert_eq!(binomial(-1, 3), Some(-1));
+    assert_eq!(binomial(-3, 2), Some(6));
+    assert_eq!(binomial(i128::MAX, 3), None);
+}
+
+#[test]
+fn test_sequence() {
+    let sequence = Sequence::new(&[10, 13, 16, 21, 30, 45]).unwrap();
+    assert_eq!(sequence.degree(), 3);
+    assert_eq!(sequence.next(1), Some(68));
+    assert_eq!(sequence.previous(1), Some(5));
+    assert_eq!(Sequence::new(&[3, 3, 3]).unwrap().degree(), 0);
+    assert_eq!(Sequence::new(&[0, 0]).unwrap().next(4), Some(0));
+    assert_eq!(Sequence::new(&[]).unwrap().previous(2), Some(0));
+    assert_eq!(Sequence::new(&[i128::MAX, i128::MIN]), None);
+    assert_eq!(Sequence::new(&[0, i128::MAX / 2]).unwrap().next(2), None);
+}
+
+#[test]
+fn test_sequence_brute_force() {
+    // Extend the difference pyramid step by step and compare.
+    let values = [-4i128, 7, 1, 22, -5, 63, 0];
+    let sequence = Sequence::new(&values).unwrap();
+    let mut rows = vec![values.to_vec()];
+    while rows.last().unwrap().iter().any(|&n| n != 0) {
+        let last = rows.last().unwrap();
+        rows.push(last.windows(2).map(|w| w[1] - w[0]).collect());
+    }
+    assert_eq!(sequence.degree(), rows.len() - 2);
+    if rows.last().unwrap().is_empty() {
+        rows.last_mut().unwrap().push(0);
+    }
+    for steps in 1..10 {
+        for i in (0..rows.len() - 1).rev() {
+            let below = *rows[i + 1].last().unwrap();
+            let last = *rows[i].last().unwrap();
+            rows[i].push(last + below);
+        }
+        assert_eq!(sequence.next(steps), rows[0].last().copied());
+    }
+}
@@ -0,0 +1,581 @@
+//! Interval arithmetic over `usize`, shared by the days that manipulate
+//! ranges of values rather than the values themselves.
+
+use std::ops::{Range, RangeInclusive};
+
+/// A half-open interval `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interval {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Interval {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Build the interval of `len` values from `start`, saturating at
+    /// `usize::MAX`.
+    pub fn with_len(start: usize, len: usize) -> Self {
+        Self::new(start, start.saturating_add(len))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        self.start <= value && value < self.end
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let interval = Self::new(self.start.max(other.start), self.end.min(other.end));
+        (!interval.is_empty()).then_some(interval)
+    }
+
+    /// Return the parts of `self` lying before and after `other`.
+    pub fn difference(&self, other: &Self) -> (Option<Self>, Option<Self>) {
+        if self.is_empty() {
+            return (None, None);
+        }
+        if self.end <= other.start {
+            return (Some(*self), None);
+        }
+        if self.start >= other.end {
+            return (None, Some(*self));
+        }
+        let before = Self::new(self.start, other.start);
+        let after = Self::new(other.end, self.end);
+        (
+            (!before.is_empty()).then_some(before),
+            (!after.is_empty()).then_some(after),
+        )
+    }
+}
+
+impl From<Range<usize>> for Interval {
+    fn from(range: Range<usize>) -> Self {
+        Self::new(range.start, range.end)
+    }
+}
+
+#[test]
+fn test_interval() {
+    let i = Interval::new(2, 6);
+    assert_eq!(i.len(), 4);
+    assert_eq!(Interval::with_len(2, 4), i);
+    assert_eq!(Interval::with_len(usize::MAX - 1, 5).len(), 1);
+    assert_eq!(
+        i.intersection(&Interval::new(4, 10)),
+        Some(Interval::new(4, 6))
+    );
+    assert_eq!(i.intersection(&Interval::new(6, 10)), None);
+    assert_eq!(
+        i.difference(&Interval::new(3, 4)),
+        (Some(Interval::new(2, 3)), Some(Interval::new(4, 6)))
+    );
+    assert_eq!(i.difference(&Interval::new(0, 10)), (None, None));
+    assert_eq!(i.difference(&Interval::new(8, 10)), (Some(i), None));
+}
+
+/// A closed interval `[lo, hi]`, empty when `lo > hi`.
+///
+/// Unlike [`Interval`] it can hold `usize::MAX`, so it suits bounds that
+/// are naturally inclusive, such as `x <= value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InclusiveInterval {
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl InclusiveInterval {
+    pub fn new(lo: usize, hi: usize) -> Self {
+        Self { lo, hi }
+    }
+
+    /// An interval holding no values.
+    pub fn empty() -> Self {
+        Self::new(1, 0)
+    }
+
+    /// The full range `[0, usize::MAX]`.
+    pub fn full() -> Self {
+        Self::new(0, usize::MAX)
+    }
+
+    /// Number of values in the interval. This is a `u128` as the full
+    /// range holds `usize::MAX + 1` values.
+    pub fn len(&self) -> u128 {
+        if self.is_empty() {
+            0
+        } else {
+            (self.hi - self.lo) as u128 + 1
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lo > self.hi
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let interval = Self::new(self.lo.max(other.lo), self.hi.min(other.hi));
+        (!interval.is_empty()).then_some(interval)
+    }
+
+    /// Return the parts of `self` lying before and after `other`.
+    pub fn difference(&self, other: &Self) -> (Option<Self>, Option<Self>) {
+        if self.is_empty() {
+            return (None, None);
+        }
+        if other.is_empty() {
+            return (Some(*self), None);
+        }
+        let before = (self.lo < other.lo).then(|| Self::new(self.lo, self.hi.min(other.lo - 1)));
+        let after = (other.hi < self.hi).then(|| Self::new(self.lo.max(other.hi + 1), self.hi));
+        (before, after)
+    }
+
+    /// The equivalent half-open interval, or `None` if `hi` is `usize::MAX`
+    /// and the interval cannot be represented as one.
+    pub fn to_half_open(self) -> Option<Interval> {
+        if self.is_empty() {
+            Some(Interval::new(self.lo, self.lo))
+        } else {
+            self.hi
+                .checked_add(1)
+                .map(|end| Interval::new(self.lo, end))
+        }
+    }
+}
+
+impl From<RangeInclusive<usize>> for InclusiveInterval {
+    fn from(range: RangeInclusive<usize>) -> Self {
+        Self::new(*range.start(), *range.end())
+    }
+}
+
+impl From<Interval> for InclusiveInterval {
+    fn from(interval: Interval) -> Self {
+        if interval.is_empty() {
+            Self::empty()
+        } else {
+            Self::new(interval.start, interval.end - 1)
+        }
+    }
+}
+
+#[test]
+fn test_inclusive_interval() {
+    let i = InclusiveInterval::from(2..=5);
+    assert_eq!(i.len(), 4);
+    assert_eq!(InclusiveInterval::empty().len(), 0);
+    assert_eq!(InclusiveInterval::full().len(), usize::MAX as u128 + 1);
+    assert!(i.contains(5) && !i.contains(6));
+    assert_eq!(InclusiveInterval::from(Interval::new(2, 6)), i);
+    assert!(InclusiveInterval::from(Interval::new(0, 0)).is_empty());
+    assert_eq!(i.to_half_open(), Some(Interval::new(2, 6)));
+    assert_eq!(InclusiveInterval::new(7, usize::MAX).to_half_open(), None);
+    assert_eq!(
+        i.intersection(&(4..=10).into()),
+        Some(InclusiveInterval::new(4, 5))
+    );
+    assert_eq!(i.intersection(&(6..=10).into()), None);
+    assert_eq!(
+        i.difference(&(3..=3).into()),
+        (Some((2..=2).into()), Some((4..=5).into()))
+    );
+    assert_eq!(i.difference(&InclusiveInterval::full()), (None, None));
+    assert_eq!(i.difference(&(8..=10).into()), (Some(i), None));
+    assert_eq!(i.difference(&(0..=1).into()), (None, Some(i)));
+    let top = InclusiveInterval::new(usize::MAX - 1, usize::MAX);
+    assert_eq!(
+        top.difference(&(usize::MAX..=usize::MAX).into()),
+        (Some((usize::MAX - 1..=usize::MAX - 1).into()), None)
+    );
+}
+
+/// A normalized set of integers stored as sorted, disjoint and
+/// non-adjacent intervals.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sort and merge overlapping or adjacent intervals, dropping empty ones.
+    pub fn normalize(mut intervals: Vec<Interval>) -> Vec<Interval> {
+        intervals.retain(|i| !i.is_empty());
+        intervals.sort_unstable();
+        let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+        for interval in intervals {
+            match merged.last_mut() {
+                Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+                _ => merged.push(interval),
+            }
+        }
+        merged
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        self.intervals.push(interval);
+        self.intervals = Self::normalize(std::mem::take(&mut self.intervals));
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Interval> + '_ {
+        self.intervals.iter().copied()
+    }
+
+    /// Total number of integers in the set.
+    pub fn len(&self) -> usize {
+        self.intervals.iter().map(Interval::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn min(&self) -> Option<usize> {
+        self.intervals.first().map(|i| i.start)
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        let idx = self.intervals.partition_point(|i| i.end <= value);
+        self.intervals.get(idx).is_some_and(|i| i.contains(value))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.iter().chain(other.iter()).collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let (mut a, mut b) = (self.intervals.iter(), other.intervals.iter());
+        let (mut x, mut y) = (a.next(), b.next());
+        let mut intervals = vec![];
+        while let (Some(i), Some(j)) = (x, y) {
+            intervals.extend(i.intersection(j));
+            if i.end <= j.end {
+                x = a.next();
+            } else {
+                y = b.next();
+            }
+        }
+        Self { intervals }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut intervals = vec![];
+        for interval in self.iter() {
+            let mut remaining = Some(interval);
+            let start = other.intervals.partition_point(|i| i.end <= interval.start);
+            for cut in &other.intervals[start..] {
+                let Some(current) = remaining else { break };
+                if cut.start >= current.end {
+                    break;
+                }
+                let (before, after) = current.difference(cut);
+                intervals.extend(before);
+                remaining = after;
+            }
+            intervals.extend(remaining);
+        }
+        Self { intervals }
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Interval>>(iter: T) -> Self {
+        Self {
+            intervals: Self::normalize(iter.into_iter().collect()),
+        }
+    }
+}
+
+#[test]
+fn test_interval_set() {
+    let set = IntervalSet::from_iter([
+        Interval::new(5, 8),
+        Interval::new(0, 2),
+        Interval::new(8, 9),
+        Interval::new(1, 3),
+        Interval::new(4, 4),
+    ]);
+    assert_eq!(set.intervals(), [Interval::new(0, 3), Interval::new(5, 9)]);
+    assert_eq!(set.len(), 7);
+    assert!(set.contains(8) && !set.contains(3));
+    let other = IntervalSet::from_iter([Interval::new(2, 6)]);
+    assert_eq!(
+        set.difference(&other).intervals(),
+        [Interval::new(0, 2), Interval::new(6, 9)]
+    );
+    assert_eq!(
+        set.intersection(&other).intervals(),
+        [Interval::new(2, 3), Interval::new(5, 6)]
+    );
+    assert_eq!(set.union(&other).intervals(), [Interval::new(0, 9)]);
+}
+
+/// A function that shifts each source interval onto a target start, and is
+/// the identity outside of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PiecewiseLinear {
+    pieces: Vec<(Interval, usize)>,
+}
+
+impl PiecewiseLinear {
+    /// Build a map from `(source, target_start)` pieces. Panics if two
+    /// sources overlap.
+    pub fn new(mut pieces: Vec<(Interval, usize)>) -> Self {
+        pieces.retain(|(source, _)| !source.is_empty());
+        pieces.sort_unstable();
+        assert!(
+            pieces.windows(2).all(|w| w[0].0.end <= w[1].0.start),
+            "overlapping sources"
+        );
+        Self { pieces }
+    }
+
+    pub fn pieces(&self) -> &[(Interval, usize)] {
+        &self.pieces
+    }
+
+    pub fn apply(&self, value: usize) -> usize {
+        let idx = self.pieces.partition_point(|(s, _)| s.end <= value);
+        match self.pieces.get(idx) {
+            Some(&(source, target)) if source.contains(value) => target + (value - source.start),
+            _ => value,
+        }
+    }
+
+    /// Cut an interval along the pieces of the map, returning each cut
+    /// with the start of its image.
+    pub fn segments(&self, interval: Interval) -> Vec<(Interval, usize)> {
+        let mut segments = vec![];
+        let mut remaining = Some(interval).filter(|i| !i.is_empty());
+        let start = self
+            .pieces
+            .partition_point(|(s, _)| s.end <= interval.start);
+        for &(source, target) in &self.pieces[start..] {
+            let Some(current) = remaining else { break };
+            let Some(mapped) = current.intersection(&source) else {
+                break;
+            };
+            let (before, after) = current.difference(&source);
+            segments.extend(before.map(|i| (i, i.start)));
+            segments.push((mapped, target + (mapped.start - source.start)));
+            remaining = after;
+        }
+        segments.extend(remaining.map(|i| (i, i.start)));
+        segments
+    }
+
+    /// Image of an interval, as the pieces it is cut into.
+    pub fn apply_interval(&self, interval: Interval) -> Vec<Interval> {
+        self.segments(interval)
+            .into_iter()
+            .map(|(source, target)| Interval::with_len(target, source.len()))
+            .collect()
+    }
+
+    pub fn apply_set(&self, set: &IntervalSet) -> IntervalSet {
+        set.iter().flat_map(|i| self.apply_interval(i)).collect()
+    }
+
+    /// The map applying `self` then `then`.
+    pub fn compose(&self, then: &Self) -> Self {
+        let mut pieces: Vec<(Interval, usize)> = vec![];
+        for (source, mid) in self.segments(Interval::new(0, usize::MAX)) {
+            for (image, target) in then.segments(Interval::with_len(mid, source.len())) {
+                let start = source.start + (image.start - mid);
+                if start == target {
+                    continue;
+                }
+                match pieces.last_mut() {
+                    Some((last, last_target))
+                        if last.end == start && *last_target + last.len() == target =>
+                    {
+                        last.end += image.len()
+                    }
+                    _ => pieces.push((Interval::with_len(start, image.len()), target)),
+                }
+            }
+        }
+        Self { pieces }
+    }
+
+    /// All the values mapped into `interval`.
+    pub fn preimage(&self, interval: Interval) -> IntervalSet {
+        self.segments(Interval::new(0, usize::MAX))
+            .into_iter()
+            .filter_map(|(source, target)| {
+                Interval::with_len(target, source.len())
+                    .intersection(&interval)
+                    .map(|i| Interval::with_len(source.start + (i.start - target), i.len()))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_piecewise_linear() {
+    let map = PiecewiseLinear::new(vec![(Interval::with_len(5, 2), 5)]);
+    assert_eq!(
+        map.apply_interval(Interval::with_len(0, 5)),
+        [Interval::with_len(0, 5)]
+    );
+    assert_eq!(
+        map.apply_interval(Interval::with_len(0, 6)),
+        [Interval::with_len(0, 5), Interval::with_len(5, 1)]
+    );
+    assert_eq!(
+        map.apply_interval(Interval::with_len(0, 10)),
+        [
+            Interval::with_len(0, 5),
+            Interval::with_len(5, 2),
+            Interval::with_len(7, 3)
+        ]
+    );
+    assert_eq!(
+        map.apply_interval(Interval::with_len(6, 4)),
+        [Interval::with_len(6, 1), Interval::with_len(7, 3)]
+    );
+    let map = PiecewiseLinear::new(vec![
+        (Interval::with_len(98, 2), 50),
+        (Interval::with_len(50, 48), 52),
+    ]);
+    assert_eq!(map.apply(79), 81);
+    assert_eq!(map.apply(99), 51);
+    assert_eq!(map.apply(13), 13);
+    let then = PiecewiseLinear::new(vec![(Interval::with_len(50, 10), 0)]);
+    let composed = map.compose(&then);
+    assert_eq!(composed.apply(98), 0);
+    assert_eq!(composed.apply(50), 2);
+    assert_eq!(composed.apply(58), 60);
+    assert_eq!(
+        map.preimage(Interval::new(49, 52)).intervals(),
+        [Interval::new(49, 50), Interval::new(98, 100)]
+    );
+}
+
+#[cfg(test)]
+use proptest::prelude::*;
+
+#[cfg(test)]
+const UNIVERSE: usize = 64;
+
+#[cfg(test)]
+fn arb_interval() -> impl Strategy<Value = Interval> {
+    (0..UNIVERSE, 0..UNIVERSE).prop_map(|(a, b)| Interval::new(a.min(b), a.max(b)))
+}
+
+#[cfg(test)]
+fn arb_set() -> impl Strategy<Value = IntervalSet> {
+    prop::collection::vec(arb_interval(), 0..6).prop_map(IntervalSet::from_iter)
+}
+
+#[cfg(test)]
+fn arb_map() -> impl Strategy<Value = PiecewiseLinear> {
+    prop::collection::vec((arb_interval(), 0..UNIVERSE), 0..6).prop_map(|pieces| {
+        let mut sources = IntervalSet::new();
+        let pieces = pieces
+            .into_iter()
+            .filter(|(source, _)| {
+                let disjoint = sources
+                    .intersection(&IntervalSet::from_iter([*source]))
+                    .is_empty();
+                if disjoint {
+                    sources.insert(*source);
+                }
+                disjoint
+            })
+            .collect();
+        PiecewiseLinear::new(pieces)
+    })
+}
+
+#[cfg(test)]
+fn members(set: &IntervalSet) -> Vec<usize> {
+    (0..2 * UNIVERSE).filter(|&v| set.contains(v)).collect()
+}
+
+#[cfg(test)]
+fn is_normalized(set: &IntervalSet) -> bool {
+    set.intervals().iter().all(|i| !i.is_empty())
+        && set.intervals().windows(2).all(|w| w[0].end < w[1].start)
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn prop_set_operations(a in arb_set(), b in arb_set()) {
+        let (ma, mb) = (members(&a), members(&b));
+        let union = a.union(&b);
+        let intersection = a.intersection(&b);
+        let difference = a.difference(&b);
+        for set in [&a, &union, &intersection, &difference] {
+            prop_assert!(is_normalized(set));
+            prop_assert_eq!(set.len(), members(set).len());
+        }
+        for v in 0..2 * UNIVERSE {
+            let (in_a, in_b) = (ma.contains(&v), mb.contains(&v));
+            prop_assert_eq!(union.contains(v), in_a || in_b);
+            prop_assert_eq!(intersection.contains(v), in_a && in_b);
+            prop_assert_eq!(difference.contains(v), in_a && !in_b);
+        }
+    }
+
+    #[test]
+    fn prop_piecewise_linear(map in arb_map(), set in arb_set()) {
+        let image = map.apply_set(&set);
+        prop_assert!(is_normalized(&image));
+        let mut expected = members(&set).into_iter().map(|v| map.apply(v)).collect::<Vec<_>>();
+        expected.sort_unstable();
+        expected.dedup();
+        prop_assert_eq!(members(&image), expected);
+        let pieces = set.iter().flat_map(|i| map.apply_interval(i)).collect::<Vec<_>>();
+        prop_assert_eq!(pieces.iter().map(Interval::len).sum::<usize>(), set.len());
+    }
+
+    #[test]
+    fn prop_compose(a in arb_map(), b in arb_map()) {
+        let composed = a.compose(&b);
+        for v in 0..2 * UNIVERSE {
+            prop_assert_eq!(composed.apply(v), b.apply(a.apply(v)));
+        }
+    }
+
+    #[test]
+    fn prop_preimage(map in arb_map(), interval in arb_interval()) {
+        let preimage = map.preimage(interval);
+        prop_assert!(is_normalized(&preimage));
+        for v in 0..2 * UNIVERSE {
+            prop_assert_eq!(preimage.contains(v), interval.contains(map.apply(v)));
+        }
+    }
+
+    #[test]
+    fn prop_apply_brute_force(map in arb_map(), v in 0..2 * UNIVERSE) {
+        let expected = map
+            .pieces()
+            .iter()
+            .find(|(source, _)| source.contains(v))
+            .map_or(v, |(source, target)| target + v - source.start);
+        prop_assert_eq!(map.apply(v), expected);
+    }
+}